@@ -0,0 +1,77 @@
+use teloxide::prelude::*;
+use teloxide::types::ChatPermissions;
+use crate::database::Database;
+use crate::error::HandlerResult;
+use crate::metrics;
+use crate::models::ActionPolicy;
+use crate::queue;
+
+// Catat strike untuk pengirim spam dan jatuhkan hukuman sesuai policy grup.
+// Mengembalikan policy yang dijalankan, atau None jika baru sebatas strike.
+pub async fn punish(bot: &Bot, db: &Database, chat_id: ChatId, user_id: UserId) -> HandlerResult<Option<ActionPolicy>> {
+    let settings = db.get_settings(chat_id.0).await?;
+    if settings.action == ActionPolicy::Delete {
+        return Ok(None);
+    }
+
+    let strikes = db.add_strike(chat_id.0, user_id.0 as i64).await?;
+    if strikes < settings.strike_limit {
+        metrics::ACTIONS.with_label_values(&["strike"]).inc();
+        return Ok(None);
+    }
+
+    match settings.action {
+        ActionPolicy::Delete => {}
+        ActionPolicy::Mute => {
+            queue::run(bot, chat_id, "mute", move |bot| async move {
+                bot.restrict_chat_member(chat_id, user_id, ChatPermissions::empty()).await.map(drop)
+            })
+            .await?;
+        }
+        ActionPolicy::Kick => {
+            // Ban lalu unban supaya user bisa join lagi nanti
+            queue::run(bot, chat_id, "kick", move |bot| async move { bot.ban_chat_member(chat_id, user_id).await.map(drop) }).await?;
+            queue::run(bot, chat_id, "kick", move |bot| async move { bot.unban_chat_member(chat_id, user_id).await.map(drop) }).await?;
+        }
+        ActionPolicy::Ban => {
+            queue::run(bot, chat_id, "ban", move |bot| async move { bot.ban_chat_member(chat_id, user_id).await.map(drop) }).await?;
+        }
+    }
+
+    metrics::ACTIONS.with_label_values(&[settings.action.as_str()]).inc();
+    db.reset_strikes(chat_id.0, user_id.0 as i64).await?;
+    log::info!("{} dijatuhkan ke {} di {}", settings.action.as_str(), user_id, chat_id);
+
+    Ok(Some(settings.action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use std::sync::Arc;
+
+    // Hanya jalur sebelum batas strike, jadi bot tidak pernah memanggil Telegram
+    #[tokio::test]
+    async fn strikes_accumulate_until_limit() {
+        let bot = Bot::new("0:test");
+        let db = Database::with_store(Arc::new(MemoryStore::default()));
+        let (chat, user) = (ChatId(1), UserId(10));
+        db.set_action(1, ActionPolicy::Ban).await.unwrap();
+        db.set_strike_limit(1, 3).await.unwrap();
+
+        assert_eq!(punish(&bot, &db, chat, user).await.ok().unwrap(), None);
+        assert_eq!(punish(&bot, &db, chat, user).await.ok().unwrap(), None);
+        assert_eq!(db.add_strike(1, 10).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn delete_policy_does_not_count_strikes() {
+        let bot = Bot::new("0:test");
+        let db = Database::with_store(Arc::new(MemoryStore::default()));
+        db.set_action(1, ActionPolicy::Delete).await.unwrap();
+
+        assert_eq!(punish(&bot, &db, ChatId(1), UserId(10)).await.ok().unwrap(), None);
+        assert_eq!(db.add_strike(1, 10).await.unwrap(), 1);
+    }
+}
@@ -1,5 +1,14 @@
+use futures_util::stream::{self, StreamExt};
 use teloxide::{prelude::*, utils::command::BotCommands};
 use crate::database::Database;
+use crate::error::{reply_on_db_error, HandlerResult};
+use crate::models::{ActionPolicy, ConfigTemplate, Federation, GroupMode, LANGUAGES, STRIKE_LIMITS};
+use crate::transfer::{ImportError, ImportMode};
+use crate::message::normalize_keyword;
+use std::sync::Arc;
+use crate::permissions::{self, Permission};
+use crate::{action, admins, classifier, queue, settings, transfer};
+use crate::owner::parse_target;
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "Command yang tersedia:")]
@@ -14,18 +23,203 @@ pub enum AdminCommand {
     Delbl(String),
     #[command(description = "Lihat semua blacklist.")]
     Listbl,
+    #[command(description = "Hapus semua keyword blacklist.")]
+    Clearbl,
     #[command(description = "Tambah keyword whitelist.")]
     Addwhite(String),
+    #[command(description = "Hapus keyword whitelist.")]
+    Delwhite(String),
     #[command(description = "Lihat semua whitelist.")]
     Listwhite,
+    #[command(description = "Hapus semua keyword whitelist.")]
+    Clearwhite,
+    #[command(description = "Hapus pesan (reply) sebagai spam, hukum pengirim, dan latih filter.")]
+    Spam,
+    #[command(description = "Tandai pesan (reply) sebagai bukan spam, pesan serupa dikecualikan.")]
+    Ham,
+    #[command(description = "Atur hukuman: delete, mute, kick, atau ban.")]
+    Setaction(String),
+    #[command(description = "Atur jumlah strike sebelum hukuman dijatuhkan.")]
+    Setstrikes(String),
+    #[command(description = "Kirim laporan tindakan ke chat/channel: /setlog <chat_id> atau /setlog off.")]
+    Setlog(String),
+    #[command(description = "Bahasa laporan log: id atau en.")]
+    Setlang(String),
+    #[command(description = "Jadikan user moderator bot tanpa hak admin Telegram: /addmod <user_id>, atau reply.")]
+    Addmod(String),
+    #[command(description = "Cabut moderator bot: /delmod <user_id>, atau reply.")]
+    Delmod(String),
+    #[command(description = "Lihat moderator bot grup ini.")]
+    Mods,
+    #[command(description = "Pakai keyword global bot: on atau off.")]
+    Globalkw(String),
+    #[command(description = "Buat federasi baru dan masukkan grup ini: /newfed <nama>.")]
+    Newfed(String),
+    #[command(description = "Buat undangan sekali pakai ke federasi grup ini (owner federasi).")]
+    Fedinvite,
+    #[command(description = "Gabungkan grup ke federasi: /joinfed <token undangan>.")]
+    Joinfed(String),
+    #[command(description = "Keluarkan grup dari federasinya.")]
+    Leavefed,
+    #[command(description = "Info federasi grup ini.")]
+    Fedinfo,
+    #[command(description = "Ban user di seluruh federasi: /fban <user_id> [alasan].")]
+    Fban(String),
+    #[command(description = "Cabut ban federasi: /unfban <user_id>.")]
+    Unfban(String),
+    #[command(description = "Bagikan blacklist grup ini ke seluruh federasi: on atau off.")]
+    Fedbl(String),
+    #[command(description = "Kelola grup dari chat pribadi: /connect [chat_id].")]
+    Connect(String),
+    #[command(description = "Putuskan koneksi chat pribadi dari grup.")]
+    Disconnect,
+    #[command(description = "Ekspor blacklist, whitelist, dan settings: /exportlists [json|csv].")]
+    Exportlists(String),
+    #[command(description = "Impor dari file (reply): /importlists [merge|replace].")]
+    Importlists(String),
+    #[command(description = "Simpan pengaturan dan daftar grup sebagai template: /savetemplate <nama>.")]
+    Savetemplate(String),
+    #[command(description = "Terapkan template milik Anda: /applytemplate <nama> [merge|replace].")]
+    Applytemplate(String),
+    #[command(description = "Lihat template milik Anda.")]
+    Templates,
+    #[command(description = "Hapus template milik Anda: /deltemplate <nama>.")]
+    Deltemplate(String),
+    #[command(description = "Salin pengaturan dan daftar dari grup lain: /clonefrom <chat_id> [merge|replace].")]
+    Clonefrom(String),
+    #[command(description = "Buka panel pengaturan.")]
+    Settings,
     #[command(description = "Tampilkan bantuan.")]
     Help,
 }
 
-async fn is_user_admin(bot: &Bot, chat_id: i64, user_id: i64) -> bool {
-    match bot.get_chat_administrators(ChatId(chat_id)).await {
-        Ok(admins) => admins.iter().any(|admin| admin.user.id.0 == user_id as u64),
-        Err(_) => false,
+impl AdminCommand {
+    // Hak admin Telegram yang dibutuhkan tiap command, lihat permissions.rs.
+    // Command yang melemahkan proteksi (menambah whitelist, membuang
+    // blacklist, mematikan keyword global) setara dengan /off.
+    fn permission(&self) -> Permission {
+        match self {
+            AdminCommand::Globalkw(arg) if arg.trim().eq_ignore_ascii_case("off") => Permission::Creator,
+            AdminCommand::Off
+            | AdminCommand::Delbl(_)
+            | AdminCommand::Clearbl
+            | AdminCommand::Addwhite(_)
+            | AdminCommand::Newfed(_)
+            | AdminCommand::Fedinvite
+            | AdminCommand::Joinfed(_)
+            | AdminCommand::Leavefed => Permission::Creator,
+            AdminCommand::Addmod(_) | AdminCommand::Delmod(_) => Permission::PromoteMembers,
+            AdminCommand::Setlog(_)
+            | AdminCommand::Setlang(_)
+            | AdminCommand::Globalkw(_)
+            | AdminCommand::Fedbl(_)
+            | AdminCommand::Importlists(_)
+            | AdminCommand::Applytemplate(_)
+            | AdminCommand::Clonefrom(_) => Permission::ChangeInfo,
+            AdminCommand::Setaction(_) | AdminCommand::Setstrikes(_) | AdminCommand::Fban(_) | AdminCommand::Unfban(_) => {
+                Permission::RestrictMembers
+            }
+            AdminCommand::On
+            | AdminCommand::Addbl(_)
+            | AdminCommand::Delwhite(_)
+            | AdminCommand::Clearwhite
+            | AdminCommand::Spam
+            | AdminCommand::Ham => Permission::DeleteMessages,
+            AdminCommand::Listbl
+            | AdminCommand::Listwhite
+            | AdminCommand::Mods
+            | AdminCommand::Fedinfo
+            | AdminCommand::Exportlists(_)
+            | AdminCommand::Savetemplate(_)
+            | AdminCommand::Templates
+            | AdminCommand::Deltemplate(_)
+            | AdminCommand::Settings
+            | AdminCommand::Connect(_)
+            | AdminCommand::Disconnect
+            | AdminCommand::Help => Permission::View,
+        }
+    }
+}
+
+// Laporan berisi nama user dan isi pesan, jadi hanya boleh diarahkan ke chat
+// pribadi pengirim sendiri atau chat yang pengirimnya admin di sana
+async fn can_use_log_chat(bot: &Bot, msg: &Message, channel: i64) -> bool {
+    if admins::is_anonymous_admin(msg) {
+        return false;
+    }
+    match msg.from() {
+        Some(user) if user.id.0 as i64 == channel => true,
+        Some(user) => admins::is_admin(bot, channel, user.id.0 as i64).await,
+        None => false,
+    }
+}
+
+// Batas pencarian grup untuk /connect tanpa argumen
+const CONNECT_SCAN_LIMIT: usize = 200;
+const CONNECT_CONCURRENCY: usize = 8;
+
+async fn handle_connect(bot: &Bot, db: &Database, msg: &Message, user_id: i64, arg: &str) -> HandlerResult {
+    // /connect di grup langsung menghubungkan ke grup tersebut
+    let target = if msg.chat.is_private() {
+        arg.trim().parse::<i64>().ok()
+    } else {
+        Some(msg.chat.id.0)
+    };
+
+    let text = match target {
+        Some(group_id) => {
+            if permissions::user_has(bot, db, group_id, user_id, Permission::View).await {
+                db.set_connection(user_id, group_id).await?;
+                format!("terhubung ke grup {}. kirim command admin lewat chat pribadi dengan bot.", group_id)
+            } else {
+                "hanya admin atau moderator bot grup tersebut yang dapat terhubung.".to_string()
+            }
+        }
+        None => {
+            // Tanpa argumen: tampilkan grup yang dikenal bot di mana user adalah
+            // admin/moderator. Setiap grup butuh getChatAdministrators (kecuali
+            // sudah di-cache), jadi jumlah grup dibatasi dan dicek beberapa sekaligus.
+            let groups = db.list_groups().await?;
+            let truncated = groups.len() > CONNECT_SCAN_LIMIT;
+            let lines: Vec<String> = stream::iter(groups.into_iter().take(CONNECT_SCAN_LIMIT))
+                .map(|group| async move {
+                    permissions::user_has(bot, db, group.group_id, user_id, Permission::View)
+                        .await
+                        .then(|| format!("/connect {} - {}", group.group_id, group.title))
+                })
+                .buffered(CONNECT_CONCURRENCY)
+                .filter_map(|line| async move { line })
+                .collect()
+                .await;
+
+            let mut text = if lines.is_empty() {
+                "tidak ada grup yang Anda kelola.".to_string()
+            } else {
+                format!("Pilih grup:\n{}", lines.join("\n"))
+            };
+            if truncated {
+                text.push_str("\n\nhanya sebagian grup yang dicek. kirim /connect di grup atau /connect <chat_id>.");
+            }
+            text
+        }
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+// Federasi grup ini, hanya jika user adalah owner-nya
+async fn owned_federation(bot: &Bot, db: &Database, msg: &Message, chat_id: i64, user_id: i64) -> HandlerResult<Option<Arc<Federation>>> {
+    match db.federation_of(chat_id).await? {
+        Some(f) if f.owner_id == user_id => Ok(Some(f)),
+        Some(_) => {
+            bot.send_message(msg.chat.id, "hanya owner federasi yang dapat menggunakan perintah ini.").await?;
+            Ok(None)
+        }
+        None => {
+            bot.send_message(msg.chat.id, "grup ini belum bergabung ke federasi.").await?;
+            Ok(None)
+        }
     }
 }
 
@@ -35,62 +229,512 @@ pub async fn handle_command(
     msg: Message,
     cmd: AdminCommand,
 ) -> ResponseResult<()> {
-    let chat_id = msg.chat.id.0;
+    let result = run_command(&bot, &db, &msg, cmd).await;
+    reply_on_db_error(&bot, msg.chat.id, result).await
+}
+
+async fn run_command(bot: &Bot, db: &Database, msg: &Message, cmd: AdminCommand) -> HandlerResult {
     let user_id = match msg.from() {
         Some(u) => u.id.0,
         None => {
-            bot.send_message(ChatId(chat_id), "tidak dapat verifikasi pengguna.").await?;
+            bot.send_message(msg.chat.id, "tidak dapat verifikasi pengguna.").await?;
+            return Ok(());
+        }
+    };
+
+    match cmd {
+        AdminCommand::Connect(arg) => return handle_connect(bot, db, msg, user_id as i64, &arg).await,
+        AdminCommand::Disconnect => {
+            db.clear_connection(user_id as i64).await?;
+            bot.send_message(msg.chat.id, "koneksi diputus.").await?;
+            return Ok(());
+        }
+        AdminCommand::Help => {
+            bot.send_message(msg.chat.id, AdminCommand::descriptions().to_string()).await?;
             return Ok(());
         }
+        _ => {}
+    }
+
+    // Di chat pribadi, command dijalankan terhadap grup yang terhubung
+    let chat_id = if msg.chat.is_private() {
+        match db.get_connection(user_id as i64).await? {
+            Some(group_id) => group_id,
+            None => {
+                bot.send_message(msg.chat.id, "belum terhubung ke grup. gunakan /connect terlebih dahulu.").await?;
+                return Ok(());
+            }
+        }
+    } else {
+        msg.chat.id.0
     };
 
-    if !is_user_admin(&bot, chat_id, user_id as i64).await {
-        bot.send_message(ChatId(chat_id), "hanya admin yang dapat menggunakan perintah ini.").await?;
+    // Hak admin selalu diverifikasi ulang terhadap grup target (dari cache
+    // admin), sesuai hak yang dibutuhkan command
+    let permission = cmd.permission();
+    if !permissions::sender_has(bot, db, msg, chat_id, permission).await {
+        bot.send_message(msg.chat.id, permission.denied_text()).await?;
+        return Ok(());
+    }
+
+    // Command berbasis reply hanya bermakna di dalam grup
+    if msg.chat.is_private() && matches!(cmd, AdminCommand::Spam | AdminCommand::Ham) {
+        bot.send_message(msg.chat.id, "gunakan perintah ini sebagai reply di dalam grup.").await?;
         return Ok(());
     }
 
     match cmd {
         AdminCommand::On => {
-            db.set_enabled(chat_id, true).await;
+            db.set_mode(chat_id, GroupMode::On).await?;
             bot.send_message(msg.chat.id, "Anti-GCast diaktifkan.").await?;
         }
         AdminCommand::Off => {
-            db.set_enabled(chat_id, false).await;
+            db.set_mode(chat_id, GroupMode::Off).await?;
             bot.send_message(msg.chat.id, "Anti-GCast dinonaktifkan.").await?;
         }
         AdminCommand::Addbl(word) => {
-            db.add_blacklist(chat_id, word.clone()).await;
-            bot.send_message(msg.chat.id, format!("ditambahkan ke blacklist: `{}`", word)).await?;
+            let word = normalize_keyword(&word);
+            let text = if word.is_empty() {
+                "keyword tidak boleh kosong.".to_string()
+            } else if db.add_blacklist(chat_id, word.clone()).await? {
+                format!("ditambahkan ke blacklist: `{}`", word)
+            } else {
+                format!("sudah ada di blacklist: `{}`", word)
+            };
+            bot.send_message(msg.chat.id, text).await?;
         }
         AdminCommand::Delbl(word) => {
-            db.remove_blacklist(chat_id, word.clone()).await;
-            bot.send_message(msg.chat.id, format!("dihapus dari blacklist: `{}`", word)).await?;
+            let word = normalize_keyword(&word);
+            let text = if db.remove_blacklist(chat_id, word.clone()).await? {
+                format!("dihapus dari blacklist: `{}`", word)
+            } else {
+                format!("tidak ditemukan di blacklist: `{}`", word)
+            };
+            bot.send_message(msg.chat.id, text).await?;
         }
         AdminCommand::Listbl => {
-            let list = db.list_blacklist(chat_id).await;
+            let list = db.list_blacklist(chat_id).await?;
             let text = if list.is_empty() {
                 "blacklist kosong.".to_string()
             } else {
-                format!("*Blacklist:*\n{}", list.iter().map(|x| format!("- {}", x)).collect::<Vec<_>>().join("\n"))
+                format!("Blacklist:\n{}", list.iter().map(|x| format!("- {}", x)).collect::<Vec<_>>().join("\n"))
             };
-            bot.send_message(msg.chat.id, text).parse_mode(teloxide::types::ParseMode::MarkdownV2).await?;
+            // Plain text: keyword bebas berisi karakter yang wajib di-escape di MarkdownV2
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Clearbl => {
+            let deleted = db.clear_blacklist(chat_id).await?;
+            bot.send_message(msg.chat.id, format!("blacklist dikosongkan ({} keyword dihapus).", deleted)).await?;
         }
         AdminCommand::Addwhite(word) => {
-            db.add_whitelist(chat_id, word.clone()).await;
-            bot.send_message(msg.chat.id, format!("ditambahkan ke whitelist: `{}`", word)).await?;
+            let word = normalize_keyword(&word);
+            let text = if word.is_empty() {
+                "keyword tidak boleh kosong.".to_string()
+            } else if db.add_whitelist(chat_id, word.clone()).await? {
+                format!("ditambahkan ke whitelist: `{}`", word)
+            } else {
+                format!("sudah ada di whitelist: `{}`", word)
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Delwhite(word) => {
+            let word = normalize_keyword(&word);
+            let text = if db.remove_whitelist(chat_id, word.clone()).await? {
+                format!("dihapus dari whitelist: `{}`", word)
+            } else {
+                format!("tidak ditemukan di whitelist: `{}`", word)
+            };
+            bot.send_message(msg.chat.id, text).await?;
         }
         AdminCommand::Listwhite => {
-            let list = db.list_whitelist(chat_id).await;
+            let list = db.list_whitelist(chat_id).await?;
             let text = if list.is_empty() {
                 "whitelist kosong.".to_string()
             } else {
-                format!("*Whitelist:*\n{}", list.iter().map(|x| format!("- {}", x)).collect::<Vec<_>>().join("\n"))
+                format!("Whitelist:\n{}", list.iter().map(|x| format!("- {}", x)).collect::<Vec<_>>().join("\n"))
             };
-            bot.send_message(msg.chat.id, text).parse_mode(teloxide::types::ParseMode::MarkdownV2).await?;
+            bot.send_message(msg.chat.id, text).await?;
         }
-        AdminCommand::Help => {
-            bot.send_message(msg.chat.id, AdminCommand::descriptions().to_string()).await?;
+        AdminCommand::Clearwhite => {
+            let deleted = db.clear_whitelist(chat_id).await?;
+            bot.send_message(msg.chat.id, format!("whitelist dikosongkan ({} keyword dihapus).", deleted)).await?;
+        }
+        AdminCommand::Spam => {
+            let target = match msg.reply_to_message() {
+                Some(m) if m.text().is_some() => m,
+                _ => {
+                    bot.send_message(msg.chat.id, "balas pesan teks yang ingin ditandai sebagai spam.").await?;
+                    return Ok(());
+                }
+            };
+            let text = target.text().unwrap_or_default();
+            let sender = target.from().map(|u| u.id);
+
+            queue::delete(bot, msg.chat.id, target.id, "manual");
+            if !db.record_example(chat_id, target.id.0, sender.map(|id| id.0 as i64), user_id as i64, text, true).await? {
+                bot.send_message(msg.chat.id, "pesan ini sudah pernah dilaporkan.").await?;
+                return Ok(());
+            }
+            db.train_classifier(chat_id, text, true).await?;
+
+            let mut reply = "pesan dihapus dan dicatat sebagai spam.".to_string();
+
+            // Admin tidak ikut dihukum walaupun pesannya ditandai spam
+            if let Some(sender) = sender {
+                if !admins::is_sender_admin(bot, target).await {
+                    if let Some(policy) = action::punish(bot, db, msg.chat.id, sender).await? {
+                        reply.push_str(&format!("\npengirim dikenai: {}.", policy.as_str()));
+                    }
+                }
+            }
+
+            let (model, blacklist) = tokio::join!(
+                db.load_classifier(chat_id),
+                db.list_blacklist(chat_id)
+            );
+            let (model, blacklist) = (model?, blacklist?);
+            let candidates = classifier::candidate_keywords(&model, &classifier::tokenize(text), &blacklist, 5);
+            if !candidates.is_empty() {
+                reply.push_str("\nkandidat keyword: ");
+                reply.push_str(&candidates.iter().map(|kw| format!("/addbl {}", kw)).collect::<Vec<_>>().join(", "));
+            }
+
+            bot.send_message(msg.chat.id, reply).await?;
+        }
+        AdminCommand::Ham => {
+            let target = match msg.reply_to_message() {
+                Some(m) if m.text().is_some() => m,
+                _ => {
+                    bot.send_message(msg.chat.id, "balas pesan teks yang bukan spam.").await?;
+                    return Ok(());
+                }
+            };
+            let text = target.text().unwrap_or_default();
+
+            let sender = target.from().map(|u| u.id.0 as i64);
+            let text = if db.record_example(chat_id, target.id.0, sender, user_id as i64, text, false).await? {
+                db.train_classifier(chat_id, text, false).await?;
+                "dicatat sebagai bukan spam, pesan serupa tidak akan dihapus."
+            } else {
+                "pesan ini sudah pernah dilaporkan."
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Setaction(arg) => {
+            let text = match ActionPolicy::parse(&arg) {
+                Some(policy) => {
+                    db.set_action(chat_id, policy).await?;
+                    format!("hukuman diatur ke: {}", policy.as_str())
+                }
+                None => "pilihan: delete, mute, kick, ban.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Setstrikes(arg) => {
+            let text = match arg.trim().parse::<i64>() {
+                Ok(limit) if STRIKE_LIMITS.contains(&limit) => {
+                    db.set_strike_limit(chat_id, limit).await?;
+                    format!("batas strike diatur ke: {}", limit)
+                }
+                _ => format!("masukkan angka {}-{}.", STRIKE_LIMITS.start(), STRIKE_LIMITS.end()),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Setlog(arg) => {
+            let text = match arg.trim().to_lowercase().as_str() {
+                "off" => {
+                    db.set_log_channel(chat_id, None).await?;
+                    "log channel dinonaktifkan.".to_string()
+                }
+                value => match value.parse::<i64>() {
+                    Ok(channel) if !can_use_log_chat(bot, msg, channel).await => {
+                        "Anda harus admin di chat tujuan (admin anonim tidak bisa diverifikasi).".to_string()
+                    }
+                    // Kirim pesan uji dulu supaya channel yang salah langsung ketahuan
+                    Ok(channel) => match bot.send_message(ChatId(channel), format!("log anti-gcast untuk grup {} aktif.", chat_id)).await {
+                        Ok(_) => {
+                            db.set_log_channel(chat_id, Some(channel)).await?;
+                            format!("log channel diatur ke: {}", channel)
+                        }
+                        Err(e) => format!("bot tidak dapat mengirim ke {}: {}", channel, e),
+                    },
+                    Err(_) => "format: /setlog <chat_id> atau /setlog off".to_string(),
+                },
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Setlang(arg) => {
+            let lang = arg.trim().to_lowercase();
+            let text = if LANGUAGES.contains(&lang.as_str()) {
+                db.set_language(chat_id, &lang).await?;
+                format!("bahasa log diatur ke: {}", lang)
+            } else {
+                format!("pilihan: {}.", LANGUAGES.join(", "))
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Addmod(arg) => {
+            let text = match parse_target(msg, &arg).0 {
+                Some(target) => {
+                    let mut moderators = db.get_settings(chat_id).await?.moderators;
+                    if moderators.contains(&target) {
+                        format!("{} sudah menjadi moderator bot.", target)
+                    } else {
+                        moderators.push(target);
+                        db.set_moderators(chat_id, &moderators).await?;
+                        format!("{} sekarang moderator bot: boleh mengelola keyword, /spam, dan hukuman.", target)
+                    }
+                }
+                None => "format: /addmod <user_id>, atau reply pesan user.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Delmod(arg) => {
+            let text = match parse_target(msg, &arg).0 {
+                Some(target) => {
+                    let mut moderators = db.get_settings(chat_id).await?.moderators;
+                    if moderators.contains(&target) {
+                        moderators.retain(|&id| id != target);
+                        db.set_moderators(chat_id, &moderators).await?;
+                        format!("{} bukan lagi moderator bot.", target)
+                    } else {
+                        format!("{} bukan moderator bot.", target)
+                    }
+                }
+                None => "format: /delmod <user_id>, atau reply pesan user.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Mods => {
+            let moderators = db.get_settings(chat_id).await?.moderators;
+            let text = if moderators.is_empty() {
+                "belum ada moderator bot.".to_string()
+            } else {
+                let lines: Vec<String> = moderators.iter().map(|id| format!("- {}", id)).collect();
+                format!("Moderator bot:\n{}", lines.join("\n"))
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Globalkw(arg) => {
+            let text = match arg.trim().to_lowercase().as_str() {
+                "on" => {
+                    db.set_use_global_keywords(chat_id, true).await?;
+                    "keyword global diaktifkan untuk grup ini."
+                }
+                "off" => {
+                    db.set_use_global_keywords(chat_id, false).await?;
+                    "keyword global dinonaktifkan untuk grup ini."
+                }
+                _ => "pilihan: on atau off.",
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Newfed(name) => {
+            let name = name.trim().to_string();
+            if name.is_empty() {
+                bot.send_message(msg.chat.id, "format: /newfed <nama>").await?;
+                return Ok(());
+            }
+
+            let federation = db.create_federation(name, user_id as i64, chat_id).await?;
+            db.join_federation(&federation.fed_id, chat_id).await?;
+            let token = db.create_fed_invite(&federation.fed_id).await?;
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "federasi {} dibuat.\nID: {}\nundangan sekali pakai (24 jam): /joinfed {}\nbuat undangan lain dengan /fedinvite.",
+                    federation.name, federation.fed_id, token
+                ),
+            )
+            .await?;
+        }
+        AdminCommand::Fedinvite => {
+            let Some(federation) = owned_federation(bot, db, msg, chat_id, user_id as i64).await? else {
+                return Ok(());
+            };
+
+            let token = db.create_fed_invite(&federation.fed_id).await?;
+            bot.send_message(
+                msg.chat.id,
+                format!("undangan sekali pakai ke federasi {} (24 jam):\n/joinfed {}", federation.name, token),
+            )
+            .await?;
+        }
+        AdminCommand::Joinfed(token) => {
+            // Hanya lewat undangan dari owner federasi, bukan fed_id yang bisa dilihat siapa saja
+            let text = match db.join_federation_with_invite(token.trim(), chat_id).await? {
+                Some(federation) => format!("grup bergabung ke federasi {}.", federation.name),
+                None => "undangan tidak valid, sudah dipakai, atau kedaluwarsa.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Leavefed => {
+            db.leave_federation(chat_id).await?;
+            bot.send_message(msg.chat.id, "grup keluar dari federasi.").await?;
+        }
+        AdminCommand::Fedinfo => {
+            let text = match db.federation_of(chat_id).await? {
+                Some(federation) => format!(
+                    "Federasi: {}\nID: {}\nOwner: {}\nJumlah grup: {}\nBagi blacklist: {}",
+                    federation.name,
+                    federation.fed_id,
+                    federation.owner_id,
+                    federation.groups.len(),
+                    match federation.blacklist_source {
+                        Some(source) if federation.share_blacklist => format!("ya, dari grup {}", source),
+                        _ => "tidak".to_string(),
+                    }
+                ),
+                None => "grup ini belum bergabung ke federasi.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Fban(arg) => {
+            let Some(federation) = owned_federation(bot, db, msg, chat_id, user_id as i64).await? else {
+                return Ok(());
+            };
+
+            let text = match parse_target(msg, &arg) {
+                (Some(target), reason) => {
+                    db.add_fed_ban(&federation.fed_id, target, reason, user_id as i64).await?;
+                    for &group in &federation.groups {
+                        let (group, target) = (ChatId(group), UserId(target as u64));
+                        queue::spawn(bot, group, "fban", move |bot| async move { bot.ban_chat_member(group, target).await.map(drop) });
+                    }
+                    format!("user {} di-ban di federasi {}.", target, federation.name)
+                }
+                (None, _) => "format: /fban <user_id> [alasan], atau reply pesan user.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Unfban(arg) => {
+            let Some(federation) = owned_federation(bot, db, msg, chat_id, user_id as i64).await? else {
+                return Ok(());
+            };
+
+            let text = match parse_target(msg, &arg) {
+                (Some(target), _) => {
+                    db.remove_fed_ban(&federation.fed_id, target).await?;
+                    format!("ban federasi untuk {} dicabut.", target)
+                }
+                (None, _) => "format: /unfban <user_id>, atau reply pesan user.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Fedbl(arg) => {
+            let Some(federation) = owned_federation(bot, db, msg, chat_id, user_id as i64).await? else {
+                return Ok(());
+            };
+
+            let text = match arg.trim().to_lowercase().as_str() {
+                "on" => {
+                    db.set_fed_share_blacklist(&federation.fed_id, true, chat_id).await?;
+                    "blacklist grup ini dibagikan ke seluruh federasi."
+                }
+                "off" => {
+                    db.set_fed_share_blacklist(&federation.fed_id, false, chat_id).await?;
+                    "blacklist tidak lagi dibagikan."
+                }
+                _ => "pilihan: on atau off.",
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Exportlists(format) => {
+            transfer::export_lists(bot, db, msg.chat.id, chat_id, &format).await?;
+        }
+        AdminCommand::Importlists(arg) => {
+            transfer::import_lists(bot, db, msg, chat_id, &arg).await?;
+        }
+        AdminCommand::Savetemplate(name) => {
+            let name = name.trim().to_lowercase();
+            let text = if name.is_empty() {
+                "format: /savetemplate <nama>".to_string()
+            } else {
+                // Nama template per user; template lain dengan nama sama milik user ini ditimpa
+                db.save_template(ConfigTemplate {
+                    id: None,
+                    name: name.clone(),
+                    owner_id: user_id as i64,
+                    snapshot: transfer::snapshot(db, chat_id).await?,
+                    updated_at: mongodb::bson::DateTime::now(),
+                })
+                .await?;
+                format!("template {} disimpan.", name)
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Applytemplate(arg) => {
+            let mut parts = arg.split_whitespace();
+            let name = parts.next().unwrap_or_default().to_lowercase();
+            let mode = ImportMode::parse(parts.next().unwrap_or_default(), ImportMode::Merge);
+            let text = match (db.get_template(user_id as i64, &name).await?, mode) {
+                (None, _) => "template tidak ditemukan.".to_string(),
+                (_, None) => "pilihan: merge (default) atau replace.".to_string(),
+                (Some(template), Some(mode)) => match transfer::apply_snapshot_as(bot, db, msg, chat_id, template.snapshot, mode).await {
+                    Ok(summary) => summary.describe(),
+                    Err(ImportError::Invalid(e)) => format!("template tidak valid: {}", e),
+                    Err(ImportError::Denied(permission)) => permission.denied_text().to_string(),
+                    Err(ImportError::Db(e)) => return Err(e.into()),
+                },
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Templates => {
+            let templates = db.list_templates(user_id as i64).await?;
+            let text = if templates.is_empty() {
+                "Anda belum punya template.".to_string()
+            } else {
+                format!(
+                    "Template:\n{}",
+                    templates
+                        .iter()
+                        .map(|t| format!("- {} ({} blacklist, {} whitelist)", t.name, t.snapshot.blacklist.len(), t.snapshot.whitelist.len()))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                )
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Deltemplate(name) => {
+            let name = name.trim().to_lowercase();
+            let text = match db.get_template(user_id as i64, &name).await? {
+                Some(_) => {
+                    db.delete_template(user_id as i64, &name).await?;
+                    format!("template {} dihapus.", name)
+                }
+                None => "template tidak ditemukan.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Clonefrom(arg) => {
+            let mut parts = arg.split_whitespace();
+            let source = parts.next().and_then(|id| id.parse::<i64>().ok());
+            let mode = ImportMode::parse(parts.next().unwrap_or_default(), ImportMode::Merge);
+
+            let text = match (source, mode) {
+                (Some(source), Some(mode)) if source != chat_id => {
+                    // User juga harus admin atau moderator bot di grup sumber
+                    if permissions::user_has(bot, db, source, user_id as i64, Permission::View).await {
+                        let snapshot = transfer::snapshot(db, source).await?;
+                        match transfer::apply_snapshot_as(bot, db, msg, chat_id, snapshot, mode).await {
+                            Ok(summary) => summary.describe(),
+                            Err(ImportError::Invalid(e)) => format!("gagal menyalin: {}", e),
+                            Err(ImportError::Denied(permission)) => permission.denied_text().to_string(),
+                            Err(ImportError::Db(e)) => return Err(e.into()),
+                        }
+                    } else {
+                        "Anda bukan admin atau moderator bot di grup sumber.".to_string()
+                    }
+                }
+                _ => "format: /clonefrom <chat_id> [merge|replace]".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        AdminCommand::Settings => {
+            settings::send_panel(bot, db, msg.chat.id, chat_id).await?;
         }
+        // Sudah ditangani sebelum resolusi grup target
+        AdminCommand::Connect(_) | AdminCommand::Disconnect | AdminCommand::Help => {}
     }
 
     Ok(())
@@ -0,0 +1,107 @@
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{ChatMember, ChatMemberUpdated};
+use crate::cache::{BoundedCache, CacheStats};
+use crate::config;
+use crate::metrics;
+
+type Admins = Arc<Vec<ChatMember>>;
+
+// Daftar admin per chat. Diisi saat pertama dibutuhkan, diperbarui dari update
+// chat_member, dan diambil ulang dari Telegram setelah cache.admin_ttl_secs.
+static ADMINS: Lazy<BoundedCache<i64, Admins>> = Lazy::new(|| {
+    let ttl = config::get().cache.admin_ttl();
+    BoundedCache::new("admins", 10_000, ttl, ttl, |_, admins| admins.len() * std::mem::size_of::<ChatMember>())
+});
+
+// Satu getChatAdministrators per chat walau banyak pesan masuk bersamaan
+static FETCHING: Lazy<DashMap<i64, Arc<tokio::sync::Mutex<()>>>> = Lazy::new(DashMap::new);
+
+pub async fn get(bot: &Bot, chat_id: i64) -> ResponseResult<Admins> {
+    if let Some(admins) = ADMINS.get(&chat_id) {
+        return Ok(admins);
+    }
+
+    let lock = FETCHING.entry(chat_id).or_default().clone();
+    let _guard = lock.lock().await;
+    // Sudah diisi oleh request lain selama menunggu
+    if let Some(admins) = ADMINS.get(&chat_id) {
+        return Ok(admins);
+    }
+
+    let result = bot.get_chat_administrators(ChatId(chat_id)).await;
+    FETCHING.remove(&chat_id);
+    let admins = Arc::new(result?);
+    ADMINS.insert(chat_id, admins.clone());
+    Ok(admins)
+}
+
+// Data admin user di chat, None jika bukan admin atau daftar gagal dimuat
+pub async fn member(bot: &Bot, chat_id: i64, user_id: i64) -> Option<ChatMember> {
+    match get(bot, chat_id).await {
+        Ok(admins) => admins.iter().find(|admin| admin.user.id.0 == user_id as u64).cloned(),
+        Err(e) => {
+            metrics::telegram_error(&e);
+            log::debug!("Gagal memuat admin {}: {}", chat_id, e);
+            None
+        }
+    }
+}
+
+pub async fn is_admin(bot: &Bot, chat_id: i64, user_id: i64) -> bool {
+    member(bot, chat_id, user_id).await.is_some()
+}
+
+// Pesan admin anonim dikirim atas nama grup itu sendiri
+pub fn is_anonymous_admin(msg: &Message) -> bool {
+    msg.sender_chat().is_some_and(|chat| chat.id == msg.chat.id)
+}
+
+// Pengirim pesan grup adalah admin, termasuk admin anonim
+pub async fn is_sender_admin(bot: &Bot, msg: &Message) -> bool {
+    if is_anonymous_admin(msg) {
+        return true;
+    }
+    if msg.chat.is_private() {
+        return false;
+    }
+    match msg.from() {
+        Some(user) => is_admin(bot, msg.chat.id.0, user.id.0 as i64).await,
+        None => false,
+    }
+}
+
+// Perbarui cache dari update chat_member/my_chat_member tanpa request baru.
+// Chat yang belum ada di cache dibiarkan; akan diambil saat dibutuhkan.
+pub fn handle_member_update(update: &ChatMemberUpdated) {
+    let member = &update.new_chat_member;
+    if !update.old_chat_member.is_privileged() && !member.is_privileged() {
+        return;
+    }
+
+    let chat_id = update.chat.id.0;
+    let Some(admins) = ADMINS.get(&chat_id) else {
+        return;
+    };
+    let mut updated: Vec<ChatMember> = admins.iter().filter(|admin| admin.user.id != member.user.id).cloned().collect();
+    if member.is_privileged() {
+        updated.push(member.clone());
+    }
+    log::debug!("Admin {} di {} diperbarui: {:?}", member.user.id, chat_id, member.status());
+    ADMINS.insert(chat_id, Arc::new(updated));
+}
+
+pub fn set_ttl(ttl: Duration) {
+    ADMINS.set_age(ttl, ttl);
+}
+
+pub fn evict_expired() -> usize {
+    ADMINS.evict_expired()
+}
+
+pub fn cache_stats() -> CacheStats {
+    ADMINS.stats()
+}
@@ -0,0 +1,82 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::io;
+use teloxide::prelude::*;
+use teloxide::types::ResponseParameters;
+use teloxide::{ApiError, RequestError};
+
+// Shim untuk method Bot API yang belum ada di teloxide 0.12. Panggilan mentah
+// hanya ada di sini; hasilnya dipetakan ke RequestError yang sama dengan
+// method teloxide, jadi retry dan metrik di queue.rs tetap berlaku. Hapus
+// begitu teloxide menyediakan method-nya sendiri.
+
+#[derive(Deserialize)]
+struct RawResponse {
+    ok: bool,
+    description: Option<String>,
+    parameters: Option<ResponseParameters>,
+}
+
+// Petakan body respons Bot API ke hasil, seperti yang dilakukan teloxide
+fn parse_response(raw: &str) -> ResponseResult<()> {
+    let response: RawResponse = serde_json::from_str(raw)
+        .map_err(|source| RequestError::InvalidJson { source, raw: raw.into() })?;
+
+    if response.ok {
+        return Ok(());
+    }
+    Err(match response.parameters {
+        Some(ResponseParameters::RetryAfter(after)) => RequestError::RetryAfter(after),
+        Some(ResponseParameters::MigrateToChatId(id)) => RequestError::MigrateToChatId(id),
+        None => {
+            let description = response.description.unwrap_or_default();
+            let error = serde_json::from_value(json!(description)).unwrap_or(ApiError::Unknown(description));
+            RequestError::Api(error)
+        }
+    })
+}
+
+async fn call(bot: &Bot, method: &str, body: serde_json::Value) -> ResponseResult<()> {
+    let url = bot
+        .api_url()
+        .join(&format!("/bot{}/{}", bot.token(), method))
+        .map_err(|e| RequestError::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+    let raw = bot.client().post(url).json(&body).send().await?.text().await?;
+    parse_response(&raw)
+}
+
+// deleteMessages (Bot API 7.0): hapus hingga 100 pesan sekaligus
+pub async fn delete_messages(bot: &Bot, chat_id: ChatId, message_ids: &[i32]) -> ResponseResult<()> {
+    call(bot, "deleteMessages", json!({ "chat_id": chat_id, "message_ids": message_ids })).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn ok_response() {
+        assert!(parse_response(r#"{"ok":true,"result":true}"#).is_ok());
+    }
+
+    #[test]
+    fn too_many_requests_maps_to_retry_after() {
+        let raw = r#"{"ok":false,"error_code":429,"description":"Too Many Requests: retry after 7","parameters":{"retry_after":7}}"#;
+        match parse_response(raw) {
+            Err(RequestError::RetryAfter(after)) => assert_eq!(after, Duration::from_secs(7)),
+            other => panic!("bukan RetryAfter: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn known_description_maps_to_api_error() {
+        let raw = r#"{"ok":false,"error_code":400,"description":"Bad Request: message to delete not found"}"#;
+        assert!(matches!(parse_response(raw), Err(RequestError::Api(ApiError::MessageToDeleteNotFound))));
+    }
+
+    #[test]
+    fn invalid_body_is_invalid_json() {
+        assert!(matches!(parse_response("<html>"), Err(RequestError::InvalidJson { .. })));
+    }
+}
@@ -0,0 +1,172 @@
+use dashmap::DashMap;
+use prometheus::IntCounter;
+use crate::metrics::CACHE_REQUESTS;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+struct Entry<V> {
+    value: V,
+    inserted: Instant,
+    last_access: Instant,
+    weight: usize,
+}
+
+// Ringkasan pemakaian satu cache untuk /memstats
+#[derive(Debug, Clone)]
+pub struct CacheStats {
+    pub name: &'static str,
+    pub entries: usize,
+    pub capacity: usize,
+    pub bytes: usize,
+}
+
+// Map konkuren dengan batas jumlah entry (LRU) dan umur (TTL).
+// `ttl` menentukan kapan data tidak lagi dianggap segar, `max_age` kapan entry
+// dibuang sama sekali. Di antaranya, data lama masih bisa dipakai sebagai
+// fallback lewat get_stale() saat database gagal.
+pub struct BoundedCache<K, V> {
+    name: &'static str,
+    map: DashMap<K, Entry<V>>,
+    capacity: usize,
+    // Dalam milidetik; atomic supaya bisa diubah saat konfigurasi dimuat ulang
+    ttl: AtomicU64,
+    max_age: AtomicU64,
+    // Perkiraan ukuran data di heap per entry, hanya untuk monitoring
+    weigher: fn(&K, &V) -> usize,
+    hits: IntCounter,
+    misses: IntCounter,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> BoundedCache<K, V> {
+    pub fn new(name: &'static str, capacity: usize, ttl: Duration, max_age: Duration, weigher: fn(&K, &V) -> usize) -> Self {
+        Self {
+            name,
+            map: DashMap::new(),
+            capacity,
+            ttl: AtomicU64::new(ttl.as_millis() as u64),
+            max_age: AtomicU64::new(max_age.as_millis() as u64),
+            weigher,
+            hits: CACHE_REQUESTS.with_label_values(&[name, "hit"]),
+            misses: CACHE_REQUESTS.with_label_values(&[name, "miss"]),
+        }
+    }
+
+    pub fn set_age(&self, ttl: Duration, max_age: Duration) {
+        self.ttl.store(ttl.as_millis() as u64, Ordering::Relaxed);
+        self.max_age.store(max_age.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_millis(self.ttl.load(Ordering::Relaxed))
+    }
+
+    fn max_age(&self) -> Duration {
+        Duration::from_millis(self.max_age.load(Ordering::Relaxed))
+    }
+
+    fn lookup(&self, key: &K, age: Duration) -> Option<V> {
+        let mut entry = self.map.get_mut(key)?;
+        if entry.inserted.elapsed() >= age {
+            return None;
+        }
+        entry.last_access = Instant::now();
+        Some(entry.value.clone())
+    }
+
+    // Nilai yang masih segar (lebih muda dari ttl)
+    pub fn get(&self, key: &K) -> Option<V> {
+        let value = self.lookup(key, self.ttl());
+        if value.is_some() {
+            self.hits.inc();
+        } else {
+            self.misses.inc();
+        }
+        value
+    }
+
+    // Nilai terakhir walaupun sudah kadaluarsa, selama belum dibuang
+    pub fn get_stale(&self, key: &K) -> Option<V> {
+        self.lookup(key, self.max_age())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let now = Instant::now();
+        let weight = std::mem::size_of::<K>() + std::mem::size_of::<Entry<V>>() + (self.weigher)(&key, &value);
+        self.map.insert(key, Entry { value, inserted: now, last_access: now, weight });
+
+        if self.map.len() > self.capacity {
+            self.evict_lru();
+        }
+    }
+
+    pub fn remove(&self, key: &K) {
+        self.map.remove(key);
+    }
+
+    pub fn clear(&self) {
+        self.map.clear();
+    }
+
+    // Anggap semua entry tidak segar tanpa membuangnya: get() memuat ulang dari
+    // database, tapi get_stale() masih punya fallback
+    pub fn expire_all(&self) {
+        let Some(stale) = Instant::now().checked_sub(self.ttl()) else {
+            self.clear();
+            return;
+        };
+        for mut entry in self.map.iter_mut() {
+            entry.inserted = entry.inserted.min(stale);
+        }
+    }
+
+    // Buang 10% entry yang paling lama tidak diakses, supaya scan tidak terjadi
+    // di setiap insert saat cache penuh
+    fn evict_lru(&self) {
+        let target = self.capacity - self.capacity / 10;
+        let excess = self.map.len().saturating_sub(target);
+        if excess == 0 {
+            return;
+        }
+
+        let mut by_access: Vec<(K, Instant)> = self.map.iter().map(|e| (e.key().clone(), e.last_access)).collect();
+        by_access.sort_by_key(|(_, last_access)| *last_access);
+        for (key, _) in by_access.into_iter().take(excess) {
+            self.map.remove(&key);
+        }
+    }
+
+    // Dipanggil task cleanup; mengembalikan jumlah entry yang dibuang
+    pub fn evict_expired(&self) -> usize {
+        let before = self.map.len();
+        let max_age = self.max_age();
+        self.map.retain(|_, entry| entry.inserted.elapsed() < max_age);
+        before.saturating_sub(self.map.len())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            name: self.name,
+            entries: self.map.len(),
+            capacity: self.capacity,
+            bytes: self.map.iter().map(|e| e.weight).sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expire_all_keeps_stale_fallback() {
+        let cache: BoundedCache<i64, i64> =
+            BoundedCache::new("test_expire", 10, Duration::from_secs(60), Duration::from_secs(3600), |_, _| 0);
+        cache.insert(1, 10);
+        assert_eq!(cache.get(&1), Some(10));
+
+        cache.expire_all();
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get_stale(&1), Some(10));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::database::Database;
+use crate::error::DbResult;
+use crate::message::normalize;
+use mongodb::bson::DateTime;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Scope untuk model global, gabungan feedback dari semua grup
+pub const GLOBAL_SCOPE: i64 = 0;
+
+// Model belum dipakai sebelum punya cukup contoh spam dan ham
+const MIN_TRAINING_DOCS: i64 = 5;
+
+// Batasi jumlah token per pesan supaya training tidak membanjiri Mongo
+const MAX_TOKENS: usize = 64;
+
+// Token yang dimuat per scope; yang paling sering muncul didahulukan
+pub const MAX_VOCABULARY: usize = 50_000;
+
+// Token yang baru muncul sekali dan tidak dilatih lagi selama ini dibuang
+const PRUNE_AFTER: Duration = Duration::from_secs(30 * 86_400);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(86_400);
+
+#[derive(Default, Clone)]
+pub struct BayesModel {
+    pub spam_docs: i64,
+    pub ham_docs: i64,
+    pub tokens: HashMap<String, (i64, i64)>,
+}
+
+impl BayesModel {
+    pub fn is_trained(&self) -> bool {
+        self.spam_docs >= MIN_TRAINING_DOCS && self.ham_docs >= MIN_TRAINING_DOCS
+    }
+
+    // Naive Bayes dengan Laplace smoothing, dihitung di log-space
+    pub fn spam_probability(&self, tokens: &[String]) -> Option<f64> {
+        if !self.is_trained() {
+            return None;
+        }
+
+        let spam_docs = self.spam_docs as f64;
+        let ham_docs = self.ham_docs as f64;
+        let mut log_odds = (spam_docs / ham_docs).ln();
+
+        for token in tokens {
+            // Token yang belum pernah dilihat tidak memberi informasi
+            if let Some(&(spam, ham)) = self.tokens.get(token) {
+                let p_spam = (spam as f64 + 1.0) / (spam_docs + 2.0);
+                let p_ham = (ham as f64 + 1.0) / (ham_docs + 2.0);
+                log_odds += (p_spam / p_ham).ln();
+            }
+        }
+
+        Some(1.0 / (1.0 + (-log_odds).exp()))
+    }
+}
+
+// Sisakan `limit` token dengan jumlah kemunculan terbanyak
+pub fn most_frequent(tokens: &HashMap<String, (i64, i64)>, limit: usize) -> HashMap<String, (i64, i64)> {
+    let mut entries: Vec<(&String, &(i64, i64))> = tokens.iter().collect();
+    if entries.len() > limit {
+        entries.sort_by_key(|(_, (spam, ham))| std::cmp::Reverse(spam + ham));
+        entries.truncate(limit);
+    }
+    entries.into_iter().map(|(token, counts)| (token.clone(), *counts)).collect()
+}
+
+// Token unik per pesan (model Bernoulli), kata pendek diabaikan
+pub fn tokenize(text: &str) -> Vec<String> {
+    let normalized = normalize(text);
+    let mut tokens: Vec<String> = normalized
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .map(|t| t.trim_matches('-'))
+        .filter(|t| t.chars().count() >= 2)
+        .map(str::to_string)
+        .collect();
+    tokens.sort();
+    tokens.dedup();
+    tokens.truncate(MAX_TOKENS);
+    tokens
+}
+
+// Model grup dipakai jika sudah cukup dilatih, jika belum jatuh ke model global
+pub async fn spam_probability(db: &Database, group_id: i64, text: &str) -> DbResult<Option<f64>> {
+    let tokens = tokenize(text);
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let group_model = db.load_classifier(group_id).await?;
+    if let Some(p) = group_model.spam_probability(&tokens) {
+        return Ok(Some(p));
+    }
+
+    Ok(db.load_classifier(GLOBAL_SCOPE).await?.spam_probability(&tokens))
+}
+
+// Model yang gagal dimuat dianggap tidak mendeteksi apa pun
+pub async fn is_spam(db: &Database, group_id: i64, text: &str, threshold: f64) -> bool {
+    match spam_probability(db, group_id, text).await {
+        Ok(p) => p.is_some_and(|p| p >= threshold),
+        Err(e) => {
+            log::warn!("Classifier tidak tersedia untuk {}: {}", group_id, e);
+            false
+        }
+    }
+}
+
+// Batas kemiripan (Jaccard) untuk menganggap pesan serupa dengan false positive
+const HAM_SIMILARITY: f64 = 0.8;
+
+pub fn is_similar(a: &[String], b: &[String]) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+
+    // Kedua slice sudah terurut dan unik dari tokenize()
+    let shared = a.iter().filter(|t| b.binary_search(t).is_ok()).count();
+    let union = a.len() + b.len() - shared;
+    shared as f64 / union as f64 >= HAM_SIMILARITY
+}
+
+// Token paling "spammy" dari pesan yang belum ada di blacklist, sebagai saran /addbl
+pub fn candidate_keywords(model: &BayesModel, tokens: &[String], blacklist: &[String], limit: usize) -> Vec<String> {
+    let mut scored: Vec<(f64, &String)> = tokens
+        .iter()
+        .filter(|t| t.chars().count() >= 3)
+        .filter(|t| !blacklist.iter().any(|kw| kw.eq_ignore_ascii_case(t)))
+        .map(|t| {
+            let (spam, ham) = model.tokens.get(t).copied().unwrap_or((0, 0));
+            ((spam as f64 + 1.0) / (ham as f64 + 1.0), t)
+        })
+        .filter(|(ratio, _)| *ratio > 1.0)
+        .collect();
+
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(limit).map(|(_, t)| t.clone()).collect()
+}
+
+// Buang token langka secara berkala supaya vocabulary tidak tumbuh tanpa batas
+pub fn spawn_pruning(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let cutoff = DateTime::from_millis(DateTime::now().timestamp_millis() - PRUNE_AFTER.as_millis() as i64);
+            match db.prune_classifier(cutoff).await {
+                Ok(0) => {}
+                Ok(removed) => log::info!("Classifier: {} token langka dibuang", removed),
+                Err(e) => log::warn!("Gagal membersihkan token classifier: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use std::sync::Arc;
+
+    fn trained_model() -> BayesModel {
+        let mut model = BayesModel { spam_docs: 10, ham_docs: 10, ..Default::default() };
+        model.tokens.insert("promo".to_string(), (9, 0));
+        model.tokens.insert("gratis".to_string(), (5, 1));
+        model.tokens.insert("rapat".to_string(), (0, 8));
+        model
+    }
+
+    #[test]
+    fn tokenize_normalizes_and_dedupes() {
+        let tokens = tokenize("  PROMO promo, Gratis!! a --vcs-- ");
+        assert_eq!(tokens, vec!["gratis", "promo", "vcs"]);
+    }
+
+    #[test]
+    fn tokenize_caps_token_count() {
+        let text: Vec<String> = (0..200).map(|i| format!("kata{}", i)).collect();
+        assert_eq!(tokenize(&text.join(" ")).len(), MAX_TOKENS);
+    }
+
+    #[test]
+    fn untrained_model_does_not_score() {
+        let model = BayesModel { spam_docs: 1, ham_docs: 10, ..Default::default() };
+        assert_eq!(model.spam_probability(&tokenize("promo")), None);
+    }
+
+    #[test]
+    fn spam_probability_follows_token_counts() {
+        let model = trained_model();
+        let spam = model.spam_probability(&tokenize("promo gratis")).unwrap();
+        let ham = model.spam_probability(&tokenize("rapat")).unwrap();
+        let unknown = model.spam_probability(&tokenize("lainnya")).unwrap();
+        assert!(spam > 0.95, "spam = {}", spam);
+        assert!(ham < 0.2, "ham = {}", ham);
+        assert!((unknown - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn similar_messages_share_most_tokens() {
+        let a = tokenize("rapat pengurus jam sembilan pagi");
+        assert!(is_similar(&a, &tokenize("Rapat pengurus jam sembilan pagi!")));
+        assert!(!is_similar(&a, &tokenize("rapat besok")));
+        assert!(!is_similar(&a, &[]));
+    }
+
+    #[test]
+    fn candidate_keywords_skip_blacklist_and_ham_tokens() {
+        let model = trained_model();
+        let tokens = tokenize("promo gratis rapat");
+        assert_eq!(candidate_keywords(&model, &tokens, &[], 5), vec!["promo", "gratis"]);
+        assert_eq!(candidate_keywords(&model, &tokens, &["PROMO".to_string()], 5), vec!["gratis"]);
+        assert_eq!(candidate_keywords(&model, &tokens, &[], 1), vec!["promo"]);
+    }
+
+    #[tokio::test]
+    async fn group_model_falls_back_to_global() {
+        let db = Database::with_store(Arc::new(MemoryStore::default()));
+        for _ in 0..MIN_TRAINING_DOCS {
+            db.train_classifier(1, "promo vcs murah", true).await.unwrap();
+            db.train_classifier(1, "rapat pengurus besok", false).await.unwrap();
+        }
+
+        // Grup 1 sudah cukup dilatih; grup 2 memakai model global
+        assert!(is_spam(&db, 1, "promo vcs", 0.9).await);
+        assert!(is_spam(&db, 2, "promo vcs", 0.9).await);
+        assert!(!is_spam(&db, 2, "rapat pengurus", 0.5).await);
+        assert_eq!(spam_probability(&db, 2, "!!").await.unwrap(), None);
+    }
+
+    #[test]
+    fn most_frequent_keeps_top_tokens() {
+        let top = most_frequent(&trained_model().tokens, 2);
+        let mut kept: Vec<&str> = top.keys().map(String::as_str).collect();
+        kept.sort();
+        assert_eq!(kept, vec!["promo", "rapat"]);
+    }
+}
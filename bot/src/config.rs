@@ -0,0 +1,538 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use url::Url;
+use crate::store::SyncMode;
+
+// Konfigurasi bot dari file TOML (CONFIG_PATH, default config.toml). Semua
+// field punya default, jadi file boleh tidak ada atau hanya berisi sebagian.
+// Setiap field bisa ditimpa env ANTIGCAST_<SECTION>_<FIELD>, mis.
+// ANTIGCAST_STORAGE_MAX_POOL_SIZE=50, dari env proses atau file .env.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bot: BotConfig,
+    pub storage: StorageConfig,
+    pub cache: CacheConfig,
+    pub detection: DetectionConfig,
+    pub ratelimit: RateLimitConfig,
+    pub webhook: WebhookConfig,
+    pub http: HttpConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    // Butuh restart
+    pub worker_threads: usize,
+    pub owner_ids: Vec<u64>,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        Self {
+            worker_threads: 4,
+            owner_ids: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Mongo,
+    Sqlite,
+    Memory,
+}
+
+// Seluruh bagian storage butuh restart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    pub mongodb_uri: String,
+    pub database: String,
+    pub sqlite_path: String,
+    pub max_pool_size: u32,
+    pub min_pool_size: u32,
+    pub max_idle_secs: u64,
+    pub server_selection_timeout_secs: u64,
+    pub migrate_on_start: bool,
+    pub cache_sync: SyncMode,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: StorageBackend::Mongo,
+            mongodb_uri: String::new(),
+            database: "antigcast".to_string(),
+            sqlite_path: "antigcast.db".to_string(),
+            max_pool_size: 20,
+            min_pool_size: 5,
+            max_idle_secs: 30,
+            server_selection_timeout_secs: 5,
+            migrate_on_start: true,
+            cache_sync: SyncMode::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    // Umur data cache database yang dianggap segar
+    pub ttl_secs: u64,
+    // Umur maksimal cache (dipakai sebagai fallback saat database gagal)
+    pub max_age_secs: u64,
+    // Umur pesan terakhir per chat untuk deteksi duplikat
+    pub message_age_secs: u64,
+    // Daftar admin per chat; juga diperbarui dari update chat_member
+    pub admin_ttl_secs: u64,
+    pub cleanup_interval_secs: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 300,
+            max_age_secs: 3600,
+            message_age_secs: 3600,
+            admin_ttl_secs: 600,
+            cleanup_interval_secs: 300,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+
+    pub fn max_age(&self) -> Duration {
+        Duration::from_secs(self.max_age_secs)
+    }
+
+    pub fn message_age(&self) -> Duration {
+        Duration::from_secs(self.message_age_secs)
+    }
+
+    pub fn admin_ttl(&self) -> Duration {
+        Duration::from_secs(self.admin_ttl_secs)
+    }
+
+    pub fn cleanup_interval(&self) -> Duration {
+        Duration::from_secs(self.cleanup_interval_secs)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DetectionConfig {
+    // Default untuk grup yang belum mengatur threshold emoji sendiri
+    pub emoji_threshold: i64,
+    // Chat dianggap high-traffic di atas jumlah pesan per detik ini
+    pub high_traffic_per_sec: u32,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            emoji_threshold: 5,
+            high_traffic_per_sec: 10,
+        }
+    }
+}
+
+// Batas request ke Telegram API lewat antrian aksi (queue.rs)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    // Batas total semua chat; Telegram membatasi sekitar 30 request per detik
+    pub global_per_sec: u32,
+    // Batas per chat. Hapus pesan saat antrian penuh digabung lewat deleteMessages.
+    pub chat_per_sec: u32,
+    // Percobaan ulang untuk error jaringan dan 429 sebelum aksi dibuang
+    pub max_retries: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            global_per_sec: 25,
+            chat_per_sec: 3,
+            max_retries: 5,
+        }
+    }
+}
+
+// Mode webhook sebagai ganti long polling. Seluruh bagian butuh restart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub enabled: bool,
+    // Alamat lokal yang didengarkan, biasanya di belakang reverse proxy
+    pub listen: String,
+    // URL publik dasar yang diteruskan ke `listen`, mis. https://bot.example.com
+    pub url: String,
+    // Dipakai sebagai path webhook dan header X-Telegram-Bot-Api-Secret-Token.
+    // Semua instance di belakang load balancer harus memakai nilai yang sama.
+    pub secret: String,
+    // Sertifikat publik (PEM) untuk sertifikat self-signed, kosong jika tidak perlu
+    pub certificate: String,
+    pub max_connections: u8,
+    pub drop_pending_updates: bool,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: "0.0.0.0:8443".to_string(),
+            url: String::new(),
+            secret: String::new(),
+            certificate: String::new(),
+            max_connections: 40,
+            drop_pending_updates: false,
+        }
+    }
+}
+
+impl WebhookConfig {
+    fn validate(&self, problems: &mut Vec<&'static str>) {
+        if !self.enabled {
+            return;
+        }
+        if self.listen.parse::<SocketAddr>().is_err() {
+            problems.push("webhook.listen harus berupa alamat ip:port");
+        }
+        if !Url::parse(&self.url).is_ok_and(|url| url.scheme() == "https") {
+            problems.push("webhook.url harus berupa URL https");
+        }
+        let secret_valid = (1..=256).contains(&self.secret.len())
+            && self.secret.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !secret_valid {
+            problems.push("webhook.secret wajib diisi, 1-256 karakter A-Z, a-z, 0-9, _ atau -");
+        }
+        if !self.certificate.is_empty() && !Path::new(&self.certificate).is_file() {
+            problems.push("webhook.certificate tidak ditemukan");
+        }
+        if !(1..=100).contains(&self.max_connections) {
+            problems.push("webhook.max_connections harus 1-100");
+        }
+    }
+}
+
+// Server HTTP monitoring (/metrics, /healthz, /readyz). Butuh restart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub enabled: bool,
+    pub listen: String,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: "127.0.0.1:9090".to_string(),
+        }
+    }
+}
+
+// Env lama yang tetap didukung sebagai alias
+const LEGACY_ENV: [(&str, &str, &str); 6] = [
+    ("OWNER_IDS", "bot", "owner_ids"),
+    ("STORAGE_BACKEND", "storage", "backend"),
+    ("MONGODB_URI", "storage", "mongodb_uri"),
+    ("SQLITE_PATH", "storage", "sqlite_path"),
+    ("MIGRATE_ON_START", "storage", "migrate_on_start"),
+    ("CACHE_SYNC", "storage", "cache_sync"),
+];
+
+// Env proses saat start, sebelum main memuat .env ke env proses. Env proses
+// tidak pernah diubah setelahnya (set_var tidak aman di runtime multi-thread).
+static PROCESS_ENV: Lazy<HashMap<String, String>> = Lazy::new(|| {
+    env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
+});
+
+const DOTENV_PATH: &str = ".env";
+
+// Sumber env override: env proses lebih diutamakan, lalu isi .env yang dibaca
+// ulang setiap kali konfigurasi dimuat
+struct EnvSource {
+    dotenv: HashMap<String, String>,
+}
+
+impl EnvSource {
+    fn load() -> Result<Self, String> {
+        Self::from_file(DOTENV_PATH)
+    }
+
+    // Parser dotenvy menangani `export`, kutip, dan komentar di akhir baris
+    fn from_file(path: &str) -> Result<Self, String> {
+        let dotenv = match dotenvy::from_path_iter(path) {
+            Ok(items) => items
+                .collect::<Result<HashMap<_, _>, _>>()
+                .map_err(|e| format!("{}: {}", path, e))?,
+            Err(e) if e.not_found() => HashMap::new(),
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        };
+        Ok(Self { dotenv })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        PROCESS_ENV.get(name).or_else(|| self.dotenv.get(name)).map(String::as_str)
+    }
+
+    fn config_path(&self) -> String {
+        self.get("CONFIG_PATH").unwrap_or("config.toml").to_string()
+    }
+}
+
+// Nilai env mengikuti tipe nilai yang ditimpa; list dipisah koma
+fn parse_env_value(current: &toml::Value, raw: &str) -> Result<toml::Value, String> {
+    let raw = raw.trim();
+    match current {
+        toml::Value::Integer(_) => raw.parse().map(toml::Value::Integer).map_err(|_| format!("'{}' bukan angka", raw)),
+        toml::Value::Float(_) => raw.parse().map(toml::Value::Float).map_err(|_| format!("'{}' bukan angka", raw)),
+        toml::Value::Boolean(_) => raw.parse().map(toml::Value::Boolean).map_err(|_| format!("'{}' bukan true/false", raw)),
+        toml::Value::Array(_) => Ok(toml::Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| item.parse().map(toml::Value::Integer).unwrap_or_else(|_| toml::Value::String(item.to_string())))
+                .collect(),
+        )),
+        _ => Ok(toml::Value::String(raw.to_string())),
+    }
+}
+
+fn apply_env_overrides(tree: &mut toml::Value, env: &EnvSource) -> Result<(), String> {
+    let Some(sections) = tree.as_table_mut() else {
+        return Ok(());
+    };
+
+    for (section, fields) in sections.iter_mut() {
+        let Some(fields) = fields.as_table_mut() else {
+            continue;
+        };
+        for (field, value) in fields.iter_mut() {
+            let name = format!("ANTIGCAST_{}_{}", section, field).to_uppercase();
+            let legacy = LEGACY_ENV.iter().find(|(_, s, f)| s == section && f == field).map(|(env, _, _)| *env);
+            // Env kosong dianggap tidak diisi
+            let raw = env.get(&name).or_else(|| legacy.and_then(|legacy| env.get(legacy)));
+            if let Some(raw) = raw.filter(|raw| !raw.trim().is_empty()) {
+                *value = parse_env_value(value, raw).map_err(|e| format!("{}: {}", name, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Config {
+    // Baca file (jika ada), terapkan env override, lalu validasi
+    fn load(path: &str, env: &EnvSource) -> Result<Self, String> {
+        let file: Self = match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        };
+
+        // Override lewat pohon TOML supaya default yang tidak ada di file juga bisa ditimpa
+        let mut tree = toml::Value::try_from(&file).map_err(|e| e.to_string())?;
+        apply_env_overrides(&mut tree, env)?;
+        let config: Self = tree.try_into().map_err(|e| e.to_string())?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+
+        if self.bot.worker_threads == 0 {
+            problems.push("bot.worker_threads minimal 1");
+        }
+        if self.storage.backend == StorageBackend::Mongo && self.storage.mongodb_uri.trim().is_empty() {
+            problems.push("storage.mongodb_uri wajib diisi untuk backend mongo (atau env MONGODB_URI)");
+        }
+        if self.storage.database.trim().is_empty() {
+            problems.push("storage.database tidak boleh kosong");
+        }
+        if self.storage.max_pool_size == 0 || self.storage.min_pool_size > self.storage.max_pool_size {
+            problems.push("storage.min_pool_size harus <= storage.max_pool_size dan max_pool_size minimal 1");
+        }
+        if self.cache.ttl_secs == 0 || self.cache.ttl_secs > self.cache.max_age_secs {
+            problems.push("cache.ttl_secs harus > 0 dan <= cache.max_age_secs");
+        }
+        if self.cache.message_age_secs == 0 {
+            problems.push("cache.message_age_secs minimal 1");
+        }
+        if self.cache.admin_ttl_secs == 0 {
+            problems.push("cache.admin_ttl_secs minimal 1");
+        }
+        if self.cache.cleanup_interval_secs < 10 {
+            problems.push("cache.cleanup_interval_secs minimal 10");
+        }
+        if self.detection.emoji_threshold < 1 {
+            problems.push("detection.emoji_threshold minimal 1");
+        }
+        if self.detection.high_traffic_per_sec == 0 {
+            problems.push("detection.high_traffic_per_sec minimal 1");
+        }
+        if self.ratelimit.global_per_sec == 0 || self.ratelimit.chat_per_sec == 0 {
+            problems.push("ratelimit.global_per_sec dan ratelimit.chat_per_sec minimal 1");
+        }
+        self.webhook.validate(&mut problems);
+        if self.http.enabled && self.http.listen.parse::<SocketAddr>().is_err() {
+            problems.push("http.listen harus berupa alamat ip:port");
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+
+    // Opsi struktural (thread runtime, storage) hanya berlaku setelah restart.
+    // Nilai lama dipertahankan dan nama opsi yang diabaikan dikembalikan.
+    fn keep_structural(&mut self, current: &Config) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+        if self.bot.worker_threads != current.bot.worker_threads {
+            self.bot.worker_threads = current.bot.worker_threads;
+            ignored.push("bot.worker_threads");
+        }
+        if self.storage != current.storage {
+            self.storage = current.storage.clone();
+            ignored.push("storage");
+        }
+        if self.webhook != current.webhook {
+            self.webhook = current.webhook.clone();
+            ignored.push("webhook");
+        }
+        if self.http != current.http {
+            self.http = current.http.clone();
+            ignored.push("http");
+        }
+        ignored
+    }
+}
+
+static CONFIG: Lazy<watch::Sender<Arc<Config>>> = Lazy::new(|| watch::channel(Arc::new(Config::default())).0);
+
+fn load_current() -> Result<Config, String> {
+    let env = EnvSource::load()?;
+    Config::load(&env.config_path(), &env)
+}
+
+// Dipanggil sekali di awal main, sebelum runtime dibuat
+pub fn init() -> Arc<Config> {
+    let config = load_current().unwrap_or_else(|e| panic!("Konfigurasi tidak valid: {}", e));
+    let config = Arc::new(config);
+    CONFIG.send_replace(config.clone());
+    config
+}
+
+pub fn get() -> Arc<Config> {
+    CONFIG.borrow().clone()
+}
+
+// Receiver yang diberi tahu setiap kali konfigurasi dimuat ulang
+pub fn subscribe() -> watch::Receiver<Arc<Config>> {
+    CONFIG.subscribe()
+}
+
+// Muat ulang file konfigurasi dan .env. Konfigurasi lama tetap dipakai jika
+// yang baru tidak valid. Mengembalikan opsi struktural yang berubah tapi butuh restart.
+pub fn reload() -> Result<Vec<&'static str>, String> {
+    let mut config = load_current()?;
+    let current = get();
+    let ignored = config.keep_structural(&current);
+    if config != *current {
+        CONFIG.send_replace(Arc::new(config));
+        log::info!("Konfigurasi dimuat ulang");
+    }
+    Ok(ignored)
+}
+
+// Interval pengecekan perubahan file konfigurasi
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+// Waktu ubah file konfigurasi dan .env. .env yang tidak valid tetap diawasi;
+// errornya dilaporkan saat dimuat ulang.
+fn watched_files() -> [Option<SystemTime>; 2] {
+    let config_path = EnvSource::load()
+        .unwrap_or_else(|_| EnvSource { dotenv: HashMap::new() })
+        .config_path();
+    let modified = |path: &str| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    [modified(&config_path), modified(DOTENV_PATH)]
+}
+
+// Muat ulang otomatis saat file konfigurasi atau .env berubah
+pub fn spawn_watcher() {
+    tokio::spawn(async {
+        let modified = watched_files;
+        let mut last = modified();
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let current = modified();
+            if current == last {
+                continue;
+            }
+            last = current;
+
+            match reload() {
+                Ok(ignored) if !ignored.is_empty() => {
+                    log::warn!("Perubahan {} baru berlaku setelah restart", ignored.join(", "));
+                }
+                Ok(_) => {}
+                Err(e) => log::error!("Konfigurasi baru tidak valid, tetap memakai yang lama: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dotenv_overrides_apply_without_touching_process_env() {
+        let path = env::temp_dir().join(format!("antigcast-test-{}.env", std::process::id()));
+        std::fs::write(
+            &path,
+            "export ANTIGCAST_TEST_CACHE_TTL_SECS=120 # komentar\nANTIGCAST_TEST_BOT_OWNER_IDS=\"1, 2\"\n",
+        )
+        .unwrap();
+        let env = EnvSource::from_file(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(env.get("ANTIGCAST_TEST_CACHE_TTL_SECS"), Some("120"));
+        assert_eq!(env.get("ANTIGCAST_TEST_BOT_OWNER_IDS"), Some("1, 2"));
+        assert!(std::env::var("ANTIGCAST_TEST_CACHE_TTL_SECS").is_err());
+
+        let current = toml::Value::Integer(300);
+        assert_eq!(parse_env_value(&current, env.get("ANTIGCAST_TEST_CACHE_TTL_SECS").unwrap()), Ok(toml::Value::Integer(120)));
+        let owners = parse_env_value(&toml::Value::Array(Vec::new()), env.get("ANTIGCAST_TEST_BOT_OWNER_IDS").unwrap());
+        assert_eq!(owners, Ok(toml::Value::Array(vec![toml::Value::Integer(1), toml::Value::Integer(2)])));
+    }
+
+    #[test]
+    fn missing_dotenv_is_empty() {
+        let env = EnvSource::from_file("/nonexistent/antigcast.env").unwrap();
+        assert!(env.dotenv.is_empty());
+    }
+}
@@ -1,202 +1,1026 @@
-use mongodb::{Client, Collection, options::{ClientOptions, FindOptions}, bson::doc};
-use crate::models::{BlacklistItem, WhitelistItem, GroupSettings};
-use futures_util::stream::StreamExt;
-use std::env;
-use dashmap::DashMap;
-use std::time::{Duration, Instant};
+use mongodb::bson::{doc, oid::ObjectId, Bson, DateTime, Document};
+use crate::error::{DbError, DbResult};
+use crate::message::normalize_keyword;
+use crate::models::{ActionPolicy, ConfigTemplate, Federation, GroupMode, GroupSettings, KnownGroup, SpamExample};
+use crate::classifier::{self, BayesModel, GLOBAL_SCOPE};
+use crate::config::{self, StorageBackend, StorageConfig};
+use crate::store::{Invalidation, ListKind, MemoryStore, MigrationReport, MongoStore, SqliteStore, Store, SyncMode};
+use crate::cache::{BoundedCache, CacheStats};
+use crate::health;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::time::Duration;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-#[derive(Clone)]
-struct CacheEntry {
-    data: Vec<String>,
-    last_updated: Instant,
+fn normalize_keywords(keywords: Vec<String>) -> Vec<String> {
+    let mut keywords: Vec<String> = keywords
+        .iter()
+        .map(|kw| normalize_keyword(kw))
+        .filter(|kw| !kw.is_empty())
+        .collect();
+    keywords.sort();
+    keywords.dedup();
+    keywords
 }
 
-#[derive(Clone)]
-struct SettingsCache {
-    enabled: bool,
-    last_updated: Instant,
+// Fail-safe untuk jalur baca: jika database gagal, pakai data cache terakhir
+// (walaupun sudah kadaluarsa) supaya proteksi grup tidak mati saat Mongo down.
+fn stale_or<T>(stale: Option<T>, error: DbError, what: &str) -> DbResult<T> {
+    match stale {
+        Some(value) => {
+            log::warn!("Database error saat memuat {}, memakai cache lama: {}", what, error);
+            Ok(value)
+        }
+        None => Err(error),
+    }
+}
+
+// Keyword bawaan untuk mengisi daftar global saat pertama kali dijalankan
+const DEFAULT_GLOBAL_KEYWORDS: [&str; 4] = ["tmo", "vcs", "vcan", "vcs-an"];
+const SEEDED_MARKER: &str = "seed_global_keywords";
+
+// Jeda sebelum sinkronisasi cache yang terputus dicoba lagi
+const SYNC_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const SYNC_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// Undangan federasi sekali pakai
+const FED_INVITE_LEN: usize = 24;
+const FED_INVITE_TTL: Duration = Duration::from_secs(86_400);
+
+// Judul grup diperbarui paling sering sekali sehari
+const SEEN_GROUP_TTL: Duration = Duration::from_secs(86_400);
+
+// Perkiraan ukuran heap untuk monitoring cache
+fn strings_size(words: &[String]) -> usize {
+    words.iter().map(|w| w.capacity() + std::mem::size_of::<String>()).sum()
+}
+
+fn model_size(model: &BayesModel) -> usize {
+    model.tokens.keys().map(|t| t.capacity() + std::mem::size_of::<(String, (i64, i64))>()).sum()
+}
+
+fn user_set_size(users: &HashSet<i64>) -> usize {
+    users.capacity() * std::mem::size_of::<i64>()
+}
+
+fn federation_size(federation: &Federation) -> usize {
+    federation.name.capacity() + federation.fed_id.capacity() + federation.groups.capacity() * std::mem::size_of::<i64>()
 }
 
 #[derive(Clone)]
 pub struct Database {
-    pub blacklist: Collection<BlacklistItem>,
-    pub whitelist: Collection<WhitelistItem>,
-    pub settings: Collection<GroupSettings>,
-    // High-performance concurrent caches
-    blacklist_cache: Arc<DashMap<i64, CacheEntry>>,
-    whitelist_cache: Arc<DashMap<i64, CacheEntry>>,
-    settings_cache: Arc<DashMap<i64, SettingsCache>>,
+    store: Arc<dyn Store>,
+    sync_mode: SyncMode,
+    // High-performance concurrent caches, dibatasi jumlah dan umurnya
+    blacklist_cache: Arc<BoundedCache<i64, Vec<String>>>,
+    whitelist_cache: Arc<BoundedCache<i64, Vec<String>>>,
+    settings_cache: Arc<BoundedCache<i64, GroupSettings>>,
+    global_cache: Arc<BoundedCache<(), Vec<String>>>,
+    classifier_cache: Arc<BoundedCache<i64, Arc<BayesModel>>>,
+    ham_cache: Arc<BoundedCache<i64, Arc<Vec<Vec<String>>>>>,
+    gban_cache: Arc<BoundedCache<(), Arc<HashSet<i64>>>>,
+    federation_cache: Arc<BoundedCache<i64, Option<Arc<Federation>>>>,
+    fban_cache: Arc<BoundedCache<String, Arc<HashSet<i64>>>>,
+    // Grup yang sudah dicatat di proses ini, supaya tidak upsert tiap pesan
+    seen_groups: Arc<BoundedCache<i64, ()>>,
+    // Persiapan awal yang belum jalan karena storage belum bisa dihubungi
+    startup_pending: Arc<AtomicBool>,
 }
 
 impl Database {
+    // Backend dipilih lewat storage.backend: mongo (default), sqlite, atau memory
+    async fn open_store(config: &StorageConfig) -> DbResult<Arc<dyn Store>> {
+        let store: Arc<dyn Store> = match config.backend {
+            StorageBackend::Sqlite => Arc::new(SqliteStore::open(&config.sqlite_path)?),
+            StorageBackend::Memory => {
+                log::warn!("Storage memory dipakai, data hilang saat bot restart");
+                Arc::new(MemoryStore::default())
+            }
+            StorageBackend::Mongo => Arc::new(MongoStore::connect(config).await?),
+        };
+        log::info!("Storage backend: {:?}", config.backend);
+        Ok(store)
+    }
+
+    // Tidak panic jika storage belum bisa dihubungi: dicoba ulang dengan
+    // backoff, dan /readyz melaporkan storage gagal sampai terhubung
     pub async fn init() -> Self {
-        dotenv::dotenv().ok();
-        let uri = env::var("MONGODB_URI").expect("MONGODB_URI must be set");
+        let config = config::get();
+        let mut backoff = Duration::from_secs(1);
+        let store = loop {
+            match Self::open_store(&config.storage).await {
+                Ok(store) => break store,
+                Err(e) => {
+                    log::error!("Gagal membuka storage, dicoba lagi dalam {}s: {}", backoff.as_secs(), e);
+                    health::set_storage(Err(e.to_string()));
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(Duration::from_secs(60));
+                }
+            }
+        };
 
-        let mut client_options = ClientOptions::parse(uri).await.unwrap();
-        // Optimasi koneksi MongoDB
-        client_options.max_pool_size = Some(20);
-        client_options.min_pool_size = Some(5);
-        client_options.max_idle_time = Some(Duration::from_secs(30));
-        client_options.server_selection_timeout = Some(Duration::from_secs(5));
+        let mut database = Self::with_store(store);
+        // Sinkronisasi cache antar instance: off (default), changestream, atau poll
+        database.sync_mode = config.storage.cache_sync;
 
-        let client = Client::with_options(client_options).unwrap();
-        let db = client.database("antigcast");
+        match database.check_storage().await {
+            Ok(()) => health::set_storage(Ok(())),
+            Err(e) => {
+                log::error!("Storage tidak bisa dihubungi, bot berjalan dalam mode degraded: {}", e);
+                health::set_storage(Err(e.to_string()));
+            }
+        }
+        database.start_cache_sync();
+        database
+    }
 
+    // Ping storage. Saat pertama kali berhasil, jalankan persiapan awal
+    // (migrasi dan keyword bawaan) yang tertunda jika storage mati saat start.
+    pub async fn check_storage(&self) -> DbResult<()> {
+        self.store.ping().await?;
+        if self.startup_pending.swap(false, Ordering::Relaxed) {
+            // Migrasi otomatis bisa dimatikan (storage.migrate_on_start = false)
+            // jika dijalankan terpisah lewat `bot migrate` saat deploy
+            if config::get().storage.migrate_on_start {
+                match self.store.migrate().await {
+                    Ok(report) => {
+                        if !report.applied.is_empty() {
+                            log::info!("Migrasi diterapkan: {}", report.applied.join(", "));
+                        }
+                        if !report.pending.is_empty() {
+                            // Dicoba lagi pada pengecekan storage berikutnya
+                            log::warn!(
+                                "Migrasi masih dijalankan instance lain, tertunda: {}",
+                                report.pending.join(", ")
+                            );
+                            self.startup_pending.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    Err(e) => log::error!("Migrasi gagal, bot tetap berjalan dengan skema lama: {}", e),
+                }
+            }
+            if let Err(e) = self.seed_global_keywords().await {
+                log::warn!("Gagal mengisi keyword global bawaan: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    // Subcommand `migrate`: hanya menerapkan migrasi, tanpa menjalankan bot
+    pub async fn migrate() -> DbResult<MigrationReport> {
+        Self::open_store(&config::get().storage).await?.migrate().await
+    }
+
+    pub fn with_store(store: Arc<dyn Store>) -> Self {
+        let cache = &config::get().cache;
+        let (ttl, max_age) = (cache.ttl(), cache.max_age());
         Self {
-            blacklist: db.collection("blacklist"),
-            whitelist: db.collection("whitelist"),
-            settings: db.collection("settings"),
-            blacklist_cache: Arc::new(DashMap::new()),
-            whitelist_cache: Arc::new(DashMap::new()),
-            settings_cache: Arc::new(DashMap::new()),
+            store,
+            sync_mode: SyncMode::Off,
+            blacklist_cache: Arc::new(BoundedCache::new("blacklist", 10_000, ttl, max_age, |_, kw| strings_size(kw))),
+            whitelist_cache: Arc::new(BoundedCache::new("whitelist", 10_000, ttl, max_age, |_, kw| strings_size(kw))),
+            settings_cache: Arc::new(BoundedCache::new("settings", 10_000, ttl, max_age, |_, _| 0)),
+            global_cache: Arc::new(BoundedCache::new("global_keywords", 1, ttl, max_age, |_, kw| strings_size(kw))),
+            // Model classifier bisa besar, jadi jumlahnya dibatasi lebih ketat
+            classifier_cache: Arc::new(BoundedCache::new("classifier", 1_000, ttl, max_age, |_, model| model_size(model))),
+            ham_cache: Arc::new(BoundedCache::new("ham_examples", 5_000, ttl, max_age, |_, sets| sets.iter().map(|set| strings_size(set)).sum())),
+            gban_cache: Arc::new(BoundedCache::new("global_bans", 1, ttl, max_age, |_, users| user_set_size(users))),
+            federation_cache: Arc::new(BoundedCache::new("federations", 10_000, ttl, max_age, |_, fed| fed.as_deref().map_or(0, federation_size))),
+            fban_cache: Arc::new(BoundedCache::new("fed_bans", 1_000, ttl, max_age, |fed_id, users| fed_id.capacity() + user_set_size(users))),
+            seen_groups: Arc::new(BoundedCache::new("seen_groups", 50_000, SEEN_GROUP_TTL, SEEN_GROUP_TTL, |_, _| 0)),
+            startup_pending: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    // Terima perubahan dari instance lain dan buang cache lokal yang terdampak
+    fn start_cache_sync(&self) {
+        if self.sync_mode == SyncMode::Off {
+            return;
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mode = self.sync_mode;
+        let database = self.clone();
+        tokio::spawn(async move {
+            let mut backoff = SYNC_MIN_BACKOFF;
+            let mut failed = false;
+            loop {
+                // Perubahan selama terputus tidak terlihat, jadi cache yang
+                // terisi di antaranya juga dianggap kedaluwarsa
+                if failed {
+                    database.expire_caches();
+                }
+                health::set_cache_sync(Ok(()));
+                let started = tokio::time::Instant::now();
+                let result = database.store.watch_changes(mode, tx.clone()).await;
+                // Receiver ditutup: tidak ada lagi yang perlu disinkronkan
+                if tx.is_closed() {
+                    return;
+                }
+
+                // Koneksi yang sempat bertahan lama dianggap pulih; backoff diulang dari awal
+                if started.elapsed() > SYNC_MAX_BACKOFF {
+                    backoff = SYNC_MIN_BACKOFF;
+                }
+
+                let reason = match result {
+                    Ok(()) => "stream ditutup server".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                log::error!("Sinkronisasi cache terputus, dicoba lagi dalam {}s: {}", backoff.as_secs(), reason);
+                health::set_cache_sync(Err(reason));
+                failed = true;
+
+                // Data lama tetap disimpan sebagai fallback jika database ikut mati
+                database.expire_caches();
+
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(SYNC_MAX_BACKOFF);
+            }
+        });
+
+        let database = self.clone();
+        tokio::spawn(async move {
+            while let Some(change) = rx.recv().await {
+                log::debug!("Invalidasi cache dari luar: {:?}", change);
+                database.invalidate(&change);
+            }
+        });
+    }
+
+    fn invalidate(&self, change: &Invalidation) {
+        match change {
+            Invalidation::Settings(Some(group_id)) => {
+                self.settings_cache.remove(group_id);
+            }
+            Invalidation::Settings(None) => self.settings_cache.clear(),
+            Invalidation::List(kind, Some(group_id)) => {
+                self.list_cache(*kind).remove(group_id);
+            }
+            Invalidation::List(kind, None) => self.list_cache(*kind).clear(),
+            Invalidation::GlobalKeywords => self.global_cache.clear(),
+            Invalidation::GlobalBans => self.gban_cache.clear(),
+            Invalidation::Federations => self.federation_cache.clear(),
+            Invalidation::FedBans(Some(fed_id)) => {
+                self.fban_cache.remove(fed_id);
+            }
+            Invalidation::FedBans(None) => self.fban_cache.clear(),
+            Invalidation::Classifier(Some(scope)) => {
+                self.classifier_cache.remove(scope);
+            }
+            Invalidation::Classifier(None) => self.classifier_cache.clear(),
+            Invalidation::Ham(Some(group_id)) => {
+                self.ham_cache.remove(group_id);
+            }
+            Invalidation::Ham(None) => self.ham_cache.clear(),
+        }
+    }
+
+    // Dipanggil setelah write berhasil: buang cache lokal lalu kabari instance lain
+    async fn changed(&self, change: Invalidation) {
+        self.invalidate(&change);
+        if self.sync_mode != SyncMode::Off {
+            if let Err(e) = self.store.publish_change(&change).await {
+                log::warn!("Gagal mengabarkan perubahan {:?}: {}", change, e);
+            }
         }
     }
 
-    pub async fn is_enabled(&self, group_id: i64) -> bool {
+    // Isi daftar global dengan keyword bawaan sekali saja, saat instalasi baru.
+    // Owner yang mengosongkan daftar tidak mendapatkannya kembali setelah restart.
+    async fn seed_global_keywords(&self) -> DbResult<()> {
+        if self.store.has_marker(SEEDED_MARKER).await? {
+            return Ok(());
+        }
+
+        // Deployment lama yang sudah mengenal grup sudah pernah diisi sebelum
+        // penanda ini ada, walaupun daftarnya sekarang kosong
+        let fresh = self.store.list_groups().await?.is_empty() && self.store.list_global_keywords().await?.is_empty();
+        if fresh {
+            for keyword in DEFAULT_GLOBAL_KEYWORDS {
+                self.store.add_global_keyword(keyword).await?;
+            }
+            self.changed(Invalidation::GlobalKeywords).await;
+        }
+
+        // Ditandai setelah berhasil, supaya pengisian yang gagal dicoba lagi
+        self.store.set_marker(SEEDED_MARKER).await
+    }
+
+    pub async fn get_settings(&self, group_id: i64) -> DbResult<GroupSettings> {
         // Check cache first
         if let Some(cached) = self.settings_cache.get(&group_id) {
-            if cached.last_updated.elapsed() < Duration::from_secs(300) { // 5 menit cache
-                return cached.enabled;
-            }
+            return Ok(cached);
         }
 
         // Load from database if not cached or expired
-        let enabled = match self.settings.find_one(doc! { "group_id": group_id }, None).await {
-            Ok(Some(s)) => s.enabled,
-            _ => false,
+        let settings = match self.store.get_settings(group_id).await {
+            Ok(Some(s)) => s,
+            Ok(None) => GroupSettings::new(group_id),
+            Err(e) => {
+                let stale = self.settings_cache.get_stale(&group_id);
+                return stale_or(stale, e, "settings");
+            }
         };
 
         // Update cache
-        self.settings_cache.insert(group_id, SettingsCache {
-            enabled,
-            last_updated: Instant::now(),
-        });
+        self.settings_cache.insert(group_id, settings.clone());
 
-        enabled
+        Ok(settings)
     }
 
-    pub async fn set_enabled(&self, group_id: i64, enable: bool) {
-        // Update database
-        let _ = self.settings
-            .update_one(
-                doc! { "group_id": group_id },
-                doc! { "$set": { "enabled": enable } },
-                mongodb::options::UpdateOptions::builder().upsert(true).build(),
-            )
-            .await;
+    pub async fn is_enabled(&self, group_id: i64) -> DbResult<bool> {
+        Ok(self.get_settings(group_id).await?.is_enabled())
+    }
 
-        // Update cache immediately
-        self.settings_cache.insert(group_id, SettingsCache {
-            enabled: enable,
-            last_updated: Instant::now(),
-        });
+    async fn update_settings(&self, group_id: i64, fields: Document) -> DbResult<()> {
+        self.store.update_settings(group_id, fields).await?;
+
+        // Invalidate cache supaya pembacaan berikutnya memuat dokumen lengkap
+        self.changed(Invalidation::Settings(Some(group_id))).await;
+        Ok(())
+    }
+
+    // Field settings dari file import/template, sudah disaring transfer::SettingsImport
+    pub async fn import_settings(&self, group_id: i64, fields: Document) -> DbResult<()> {
+        self.update_settings(group_id, fields).await
+    }
+
+    pub async fn set_action(&self, group_id: i64, action: ActionPolicy) -> DbResult<()> {
+        self.update_settings(group_id, doc! { "action": action.as_str() }).await
+    }
+
+    pub async fn set_mode(&self, group_id: i64, mode: GroupMode) -> DbResult<()> {
+        self.update_settings(group_id, doc! { "mode": mode.as_str() }).await
     }
 
-    pub async fn add_blacklist(&self, group_id: i64, keyword: String) {
-        let item = BlacklistItem { id: None, group_id, keyword: keyword.clone() };
-        let _ = self.blacklist.insert_one(item, None).await;
+    pub async fn set_detector(&self, group_id: i64, detector: &str, enable: bool) -> DbResult<()> {
+        self.update_settings(group_id, doc! { format!("detectors.{}", detector): enable }).await
+    }
+
+    // Threshold disimpan apa adanya; validasi rentang dilakukan pemanggil
+    pub async fn set_threshold(&self, group_id: i64, field: &str, value: Bson) -> DbResult<()> {
+        self.update_settings(group_id, doc! { field: value }).await
+    }
+
+    pub async fn set_use_global_keywords(&self, group_id: i64, enable: bool) -> DbResult<()> {
+        self.update_settings(group_id, doc! { "use_global_keywords": enable }).await
+    }
+
+    pub async fn set_language(&self, group_id: i64, language: &str) -> DbResult<()> {
+        self.update_settings(group_id, doc! { "language": language }).await
+    }
+
+    pub async fn set_log_channel(&self, group_id: i64, channel: Option<i64>) -> DbResult<()> {
+        self.update_settings(group_id, doc! { "log_channel": channel }).await
+    }
+
+    pub async fn set_moderators(&self, group_id: i64, moderators: &[i64]) -> DbResult<()> {
+        self.update_settings(group_id, doc! { "moderators": moderators }).await
+    }
+
+    pub async fn set_strike_limit(&self, group_id: i64, limit: i64) -> DbResult<()> {
+        self.update_settings(group_id, doc! { "strike_limit": limit }).await
+    }
+
+    // Tambah strike dan kembalikan jumlah terbaru
+    pub async fn add_strike(&self, group_id: i64, user_id: i64) -> DbResult<i64> {
+        self.store.add_strike(group_id, user_id).await
+    }
+
+    pub async fn reset_strikes(&self, group_id: i64, user_id: i64) -> DbResult<()> {
+        self.store.reset_strikes(group_id, user_id).await
+    }
+
+    fn list_cache(&self, kind: ListKind) -> &BoundedCache<i64, Vec<String>> {
+        match kind {
+            ListKind::Blacklist => &self.blacklist_cache,
+            ListKind::Whitelist => &self.whitelist_cache,
+        }
+    }
+
+    // Mengembalikan false jika keyword (setelah normalisasi) sudah ada
+    async fn add_keyword(&self, kind: ListKind, group_id: i64, keyword: String) -> DbResult<bool> {
+        let keyword = normalize_keyword(&keyword);
+        let inserted = self.store.add_keyword(kind, group_id, &keyword).await?;
 
         // Invalidate cache untuk refresh
-        self.blacklist_cache.remove(&group_id);
+        self.changed(Invalidation::List(kind, Some(group_id))).await;
+        Ok(inserted)
+    }
+
+    // Mengembalikan false jika keyword tidak ditemukan
+    async fn remove_keyword(&self, kind: ListKind, group_id: i64, keyword: String) -> DbResult<bool> {
+        let keyword = normalize_keyword(&keyword);
+        let removed = self.store.remove_keyword(kind, group_id, &keyword).await?;
+
+        // Invalidate cache
+        self.changed(Invalidation::List(kind, Some(group_id))).await;
+        Ok(removed)
     }
 
-    pub async fn remove_blacklist(&self, group_id: i64, keyword: String) {
-        let _ = self.blacklist
-            .delete_one(doc! { "group_id": group_id, "keyword": &keyword }, None)
-            .await;
+    async fn add_keywords(&self, kind: ListKind, group_id: i64, keywords: Vec<String>) -> DbResult<()> {
+        let keywords = normalize_keywords(keywords);
+        if keywords.is_empty() {
+            return Ok(());
+        }
+
+        self.store.add_keywords(kind, group_id, keywords).await?;
 
         // Invalidate cache
-        self.blacklist_cache.remove(&group_id);
+        self.changed(Invalidation::List(kind, Some(group_id))).await;
+        Ok(())
     }
 
-    pub async fn list_blacklist(&self, group_id: i64) -> Vec<String> {
+    async fn clear_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<u64> {
+        let deleted = self.store.clear_keywords(kind, group_id).await?;
+
+        // Invalidate cache
+        self.changed(Invalidation::List(kind, Some(group_id))).await;
+        Ok(deleted)
+    }
+
+    async fn list_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<Vec<String>> {
+        let cache = self.list_cache(kind);
+
         // Check cache first
-        if let Some(cached) = self.blacklist_cache.get(&group_id) {
-            if cached.last_updated.elapsed() < Duration::from_secs(300) {
-                return cached.data.clone();
+        if let Some(cached) = cache.get(&group_id) {
+            return Ok(cached);
+        }
+
+        let keywords = match self.store.list_keywords(kind, group_id).await {
+            Ok(keywords) => keywords,
+            Err(e) => {
+                let stale = cache.get_stale(&group_id);
+                return stale_or(stale, e, kind.as_str());
             }
+        };
+
+        // Update cache
+        cache.insert(group_id, keywords.clone());
+
+        Ok(keywords)
+    }
+
+    pub async fn add_blacklist(&self, group_id: i64, keyword: String) -> DbResult<bool> {
+        self.add_keyword(ListKind::Blacklist, group_id, keyword).await
+    }
+
+    pub async fn remove_blacklist(&self, group_id: i64, keyword: String) -> DbResult<bool> {
+        self.remove_keyword(ListKind::Blacklist, group_id, keyword).await
+    }
+
+    pub async fn add_blacklist_many(&self, group_id: i64, keywords: Vec<String>) -> DbResult<()> {
+        self.add_keywords(ListKind::Blacklist, group_id, keywords).await
+    }
+
+    pub async fn clear_blacklist(&self, group_id: i64) -> DbResult<u64> {
+        self.clear_keywords(ListKind::Blacklist, group_id).await
+    }
+
+    pub async fn list_blacklist(&self, group_id: i64) -> DbResult<Vec<String>> {
+        self.list_keywords(ListKind::Blacklist, group_id).await
+    }
+
+    pub async fn add_whitelist(&self, group_id: i64, keyword: String) -> DbResult<bool> {
+        self.add_keyword(ListKind::Whitelist, group_id, keyword).await
+    }
+
+    pub async fn remove_whitelist(&self, group_id: i64, keyword: String) -> DbResult<bool> {
+        self.remove_keyword(ListKind::Whitelist, group_id, keyword).await
+    }
+
+    pub async fn add_whitelist_many(&self, group_id: i64, keywords: Vec<String>) -> DbResult<()> {
+        self.add_keywords(ListKind::Whitelist, group_id, keywords).await
+    }
+
+    pub async fn clear_whitelist(&self, group_id: i64) -> DbResult<u64> {
+        self.clear_keywords(ListKind::Whitelist, group_id).await
+    }
+
+    pub async fn list_whitelist(&self, group_id: i64) -> DbResult<Vec<String>> {
+        self.list_keywords(ListKind::Whitelist, group_id).await
+    }
+
+    // Training incremental: contoh dari grup juga masuk ke model global
+    pub async fn train_classifier(&self, group_id: i64, text: &str, is_spam: bool) -> DbResult<()> {
+        let tokens = classifier::tokenize(text);
+        if tokens.is_empty() {
+            return Ok(());
+        }
+
+        for scope in [group_id, GLOBAL_SCOPE] {
+            self.store.train_classifier(scope, &tokens, is_spam).await?;
+
+            // Invalidate cache supaya model berikutnya memakai data terbaru
+            self.changed(Invalidation::Classifier(Some(scope))).await;
         }
 
-        // Load from database with optimized query
-        let find_options = FindOptions::builder()
-            .projection(doc! { "keyword": 1, "_id": 0 })
-            .build();
+        Ok(())
+    }
 
-        let mut cursor = match self.blacklist
-            .find(doc! { "group_id": group_id }, find_options)
-            .await {
-                Ok(cursor) => cursor,
-                Err(_) => return Vec::new(),
-            };
+    pub async fn load_classifier(&self, scope: i64) -> DbResult<Arc<BayesModel>> {
+        // Check cache first
+        if let Some(cached) = self.classifier_cache.get(&scope) {
+            return Ok(cached);
+        }
 
-        let mut keywords = Vec::new();
-        while let Some(result) = cursor.next().await {
-            if let Ok(item) = result {
-                keywords.push(item.keyword);
+        let model = match self.store.load_classifier(scope).await {
+            Ok(model) => Arc::new(model),
+            Err(e) => {
+                let stale = self.classifier_cache.get_stale(&scope);
+                return stale_or(stale, e, "classifier");
             }
+        };
+
+        // Update cache
+        self.classifier_cache.insert(scope, model.clone());
+
+        Ok(model)
+    }
+
+    // Mengembalikan false jika pesan ini sudah pernah dicatat; pemanggil tidak
+    // perlu melatih classifier lagi supaya laporan berulang tidak menggelembungkan model
+    pub async fn record_example(&self, group_id: i64, message_id: i32, sender_id: Option<i64>, reported_by: i64, text: &str, is_spam: bool) -> DbResult<bool> {
+        let example = SpamExample {
+            id: None,
+            group_id,
+            sender_id,
+            message_id: Some(message_id),
+            reported_by,
+            text: text.to_string(),
+            is_spam,
+            created_at: DateTime::now(),
+        };
+        if !self.store.record_example(example).await? {
+            return Ok(false);
+        }
+
+        if !is_spam {
+            // Invalidate cache pengecualian ham
+            self.changed(Invalidation::Ham(Some(group_id))).await;
         }
+        Ok(true)
+    }
+
+    pub async fn prune_classifier(&self, cutoff: DateTime) -> DbResult<u64> {
+        let removed = self.store.prune_classifier(cutoff).await?;
+        if removed > 0 {
+            self.changed(Invalidation::Classifier(None)).await;
+        }
+        Ok(removed)
+    }
+
+    // Token set dari semua false positive grup, dipakai untuk mengecualikan pesan serupa
+    pub async fn list_ham_examples(&self, group_id: i64) -> DbResult<Arc<Vec<Vec<String>>>> {
+        // Check cache first
+        if let Some(cached) = self.ham_cache.get(&group_id) {
+            return Ok(cached);
+        }
+
+        let token_sets = match self.store.list_ham_texts(group_id).await {
+            Ok(texts) => Arc::new(texts.iter().map(|t| classifier::tokenize(t)).collect::<Vec<_>>()),
+            Err(e) => {
+                let stale = self.ham_cache.get_stale(&group_id);
+                return stale_or(stale, e, "ham examples");
+            }
+        };
 
         // Update cache
-        self.blacklist_cache.insert(group_id, CacheEntry {
-            data: keywords.clone(),
-            last_updated: Instant::now(),
-        });
+        self.ham_cache.insert(group_id, token_sets.clone());
 
-        keywords
+        Ok(token_sets)
     }
 
-    pub async fn add_whitelist(&self, group_id: i64, keyword: String) {
-        let item = WhitelistItem { id: None, group_id, keyword: keyword.clone() };
-        let _ = self.whitelist.insert_one(item, None).await;
+    pub async fn is_ham_exempt(&self, group_id: i64, text: &str) -> DbResult<bool> {
+        let examples = self.list_ham_examples(group_id).await?;
+        if examples.is_empty() {
+            return Ok(false);
+        }
+
+        let tokens = classifier::tokenize(text);
+        Ok(examples.iter().any(|ham| classifier::is_similar(&tokens, ham)))
+    }
+
+    pub async fn add_global_keyword(&self, keyword: String) -> DbResult<bool> {
+        let keyword = normalize_keyword(&keyword);
+        let inserted = self.store.add_global_keyword(&keyword).await?;
 
         // Invalidate cache
-        self.whitelist_cache.remove(&group_id);
+        self.changed(Invalidation::GlobalKeywords).await;
+        Ok(inserted)
     }
 
-    pub async fn list_whitelist(&self, group_id: i64) -> Vec<String> {
+    pub async fn remove_global_keyword(&self, keyword: String) -> DbResult<bool> {
+        let keyword = normalize_keyword(&keyword);
+        let removed = self.store.remove_global_keyword(&keyword).await?;
+
+        // Invalidate cache
+        self.changed(Invalidation::GlobalKeywords).await;
+        Ok(removed)
+    }
+
+    pub async fn list_global_keywords(&self) -> DbResult<Vec<String>> {
         // Check cache first
-        if let Some(cached) = self.whitelist_cache.get(&group_id) {
-            if cached.last_updated.elapsed() < Duration::from_secs(300) {
-                return cached.data.clone();
+        if let Some(cached) = self.global_cache.get(&()) {
+            return Ok(cached);
+        }
+
+        let keywords = match self.store.list_global_keywords().await {
+            Ok(keywords) => keywords,
+            Err(e) => {
+                let stale = self.global_cache.get_stale(&());
+                return stale_or(stale, e, "keyword global");
             }
+        };
+
+        // Update cache
+        self.global_cache.insert((), keywords.clone());
+
+        Ok(keywords)
+    }
+
+    // Blacklist grup ditambah keyword global, kecuali grup memilih opt-out
+    pub async fn effective_blacklist(&self, group_id: i64) -> DbResult<Vec<String>> {
+        let (settings, keywords) = tokio::join!(
+            self.get_settings(group_id),
+            self.list_blacklist(group_id)
+        );
+        let (settings, mut keywords) = (settings?, keywords?);
+
+        if settings.use_global_keywords {
+            keywords.extend(self.list_global_keywords().await?);
         }
 
-        // Load from database with optimized query
-        let find_options = FindOptions::builder()
-            .projection(doc! { "keyword": 1, "_id": 0 })
-            .build();
+        // Federasi dengan share_blacklist hanya membagikan blacklist grup sumber
+        // pilihan owner, bukan blacklist setiap anggota
+        if let Some(federation) = self.federation_of(group_id).await? {
+            if let Some(source) = federation.blacklist_source.filter(|&source| {
+                federation.share_blacklist && source != group_id && federation.groups.contains(&source)
+            }) {
+                keywords.extend(self.list_blacklist(source).await?);
+            }
+        }
+
+        Ok(keywords)
+    }
+
+    pub async fn track_group(&self, group_id: i64, title: &str) -> DbResult<()> {
+        if self.seen_groups.get(&group_id).is_some() {
+            return Ok(());
+        }
+
+        self.store.track_group(group_id, title).await?;
+
+        // Ditandai setelah berhasil, supaya kegagalan dicoba lagi di pesan berikutnya
+        self.seen_groups.insert(group_id, ());
+        Ok(())
+    }
+
+    pub async fn forget_group(&self, group_id: i64) -> DbResult<()> {
+        self.store.forget_group(group_id).await?;
+        self.seen_groups.remove(&group_id);
+        Ok(())
+    }
+
+    pub async fn list_groups(&self) -> DbResult<Vec<KnownGroup>> {
+        self.store.list_groups().await
+    }
+
+    pub async fn add_global_ban(&self, user_id: i64, reason: String, banned_by: i64) -> DbResult<()> {
+        self.store.add_global_ban(user_id, &reason, banned_by).await?;
+
+        // Invalidate cache
+        self.changed(Invalidation::GlobalBans).await;
+        Ok(())
+    }
+
+    pub async fn remove_global_ban(&self, user_id: i64) -> DbResult<()> {
+        self.store.remove_global_ban(user_id).await?;
 
-        let mut cursor = match self.whitelist
-            .find(doc! { "group_id": group_id }, find_options)
-            .await {
-                Ok(cursor) => cursor,
-                Err(_) => return Vec::new(),
-            };
+        // Invalidate cache
+        self.changed(Invalidation::GlobalBans).await;
+        Ok(())
+    }
 
-        let mut keywords = Vec::new();
-        while let Some(result) = cursor.next().await {
-            if let Ok(item) = result {
-                keywords.push(item.keyword);
+    pub async fn is_globally_banned(&self, user_id: i64) -> DbResult<bool> {
+        // Check cache first
+        if let Some(cached) = self.gban_cache.get(&()) {
+            return Ok(cached.contains(&user_id));
+        }
+
+        let users = match self.store.list_global_bans().await {
+            Ok(users) => Arc::new(users),
+            Err(e) => {
+                let stale = self.gban_cache.get_stale(&());
+                return stale_or(stale, e, "global ban").map(|users| users.contains(&user_id));
             }
+        };
+
+        let banned = users.contains(&user_id);
+
+        // Update cache
+        self.gban_cache.insert((), users);
+
+        Ok(banned)
+    }
+
+    // Semua cache harus dimuat ulang, tapi data lama tetap bisa dipakai
+    // stale_or() selama database gagal. Dipakai juga /reload supaya perubahan
+    // langsung di database segera terlihat.
+    pub fn expire_caches(&self) {
+        self.blacklist_cache.expire_all();
+        self.whitelist_cache.expire_all();
+        self.settings_cache.expire_all();
+        self.classifier_cache.expire_all();
+        self.ham_cache.expire_all();
+        self.global_cache.expire_all();
+        self.gban_cache.expire_all();
+        self.federation_cache.expire_all();
+        self.fban_cache.expire_all();
+    }
+
+    // Dipanggil task cleanup berkala; mengembalikan jumlah entry yang dibuang
+    pub fn evict_expired(&self) -> usize {
+        self.blacklist_cache.evict_expired()
+            + self.whitelist_cache.evict_expired()
+            + self.settings_cache.evict_expired()
+            + self.global_cache.evict_expired()
+            + self.classifier_cache.evict_expired()
+            + self.ham_cache.evict_expired()
+            + self.gban_cache.evict_expired()
+            + self.federation_cache.evict_expired()
+            + self.fban_cache.evict_expired()
+            + self.seen_groups.evict_expired()
+    }
+
+    // Dipanggil saat konfigurasi dimuat ulang; seen_groups punya umur sendiri
+    pub fn set_cache_age(&self, ttl: Duration, max_age: Duration) {
+        self.blacklist_cache.set_age(ttl, max_age);
+        self.whitelist_cache.set_age(ttl, max_age);
+        self.settings_cache.set_age(ttl, max_age);
+        self.global_cache.set_age(ttl, max_age);
+        self.classifier_cache.set_age(ttl, max_age);
+        self.ham_cache.set_age(ttl, max_age);
+        self.gban_cache.set_age(ttl, max_age);
+        self.federation_cache.set_age(ttl, max_age);
+        self.fban_cache.set_age(ttl, max_age);
+    }
+
+    pub fn cache_stats(&self) -> Vec<CacheStats> {
+        vec![
+            self.blacklist_cache.stats(),
+            self.whitelist_cache.stats(),
+            self.settings_cache.stats(),
+            self.global_cache.stats(),
+            self.classifier_cache.stats(),
+            self.ham_cache.stats(),
+            self.gban_cache.stats(),
+            self.federation_cache.stats(),
+            self.fban_cache.stats(),
+            self.seen_groups.stats(),
+        ]
+    }
+
+    // Grup pembuat menjadi sumber blacklist bersama
+    pub async fn create_federation(&self, name: String, owner_id: i64, group_id: i64) -> DbResult<Federation> {
+        let federation = Federation {
+            id: None,
+            fed_id: ObjectId::new().to_hex(),
+            name,
+            owner_id,
+            groups: Vec::new(),
+            share_blacklist: false,
+            blacklist_source: Some(group_id),
+        };
+
+        self.store.create_federation(&federation).await?;
+        Ok(federation)
+    }
+
+    pub async fn get_federation(&self, fed_id: &str) -> DbResult<Option<Federation>> {
+        self.store.get_federation(fed_id).await
+    }
+
+    // Federasi tempat grup bergabung (maksimal satu)
+    pub async fn federation_of(&self, group_id: i64) -> DbResult<Option<Arc<Federation>>> {
+        // Check cache first
+        if let Some(cached) = self.federation_cache.get(&group_id) {
+            return Ok(cached);
         }
 
+        let federation = match self.store.federation_of(group_id).await {
+            Ok(federation) => federation.map(Arc::new),
+            Err(e) => {
+                let stale = self.federation_cache.get_stale(&group_id);
+                return stale_or(stale, e, "federasi");
+            }
+        };
+
         // Update cache
-        self.whitelist_cache.insert(group_id, CacheEntry {
-            data: keywords.clone(),
-            last_updated: Instant::now(),
-        });
+        self.federation_cache.insert(group_id, federation.clone());
 
-        keywords
+        Ok(federation)
+    }
+
+    pub async fn join_federation(&self, fed_id: &str, group_id: i64) -> DbResult<()> {
+        // Grup hanya boleh ada di satu federasi
+        self.leave_federation(group_id).await?;
+        self.store.add_federation_group(fed_id, group_id).await?;
+
+        // Invalidate cache
+        self.changed(Invalidation::Federations).await;
+        Ok(())
+    }
+
+    // Token acak sekali pakai untuk /joinfed, berlaku FED_INVITE_TTL
+    pub async fn create_fed_invite(&self, fed_id: &str) -> DbResult<String> {
+        let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(FED_INVITE_LEN).map(char::from).collect();
+        let expires_at = DateTime::from_millis(DateTime::now().timestamp_millis() + FED_INVITE_TTL.as_millis() as i64);
+        self.store.create_fed_invite(&token, fed_id, expires_at).await?;
+        Ok(token)
+    }
+
+    // Gabung lewat undangan. Token langsung hangus walaupun federasinya sudah dihapus.
+    pub async fn join_federation_with_invite(&self, token: &str, group_id: i64) -> DbResult<Option<Federation>> {
+        let Some(fed_id) = self.store.take_fed_invite(token).await? else {
+            return Ok(None);
+        };
+        let Some(federation) = self.get_federation(&fed_id).await? else {
+            return Ok(None);
+        };
+        self.join_federation(&federation.fed_id, group_id).await?;
+        Ok(Some(federation))
+    }
+
+    pub async fn leave_federation(&self, group_id: i64) -> DbResult<()> {
+        self.store.remove_federation_group(group_id).await?;
+
+        // Invalidate cache
+        self.changed(Invalidation::Federations).await;
+        Ok(())
+    }
+
+    pub async fn set_fed_share_blacklist(&self, fed_id: &str, share: bool, source: i64) -> DbResult<()> {
+        self.store.set_fed_share_blacklist(fed_id, share, source).await?;
+
+        // Invalidate cache
+        self.changed(Invalidation::Federations).await;
+        Ok(())
+    }
+
+    pub async fn add_fed_ban(&self, fed_id: &str, user_id: i64, reason: String, banned_by: i64) -> DbResult<()> {
+        self.store.add_fed_ban(fed_id, user_id, &reason, banned_by).await?;
+
+        // Invalidate cache
+        self.changed(Invalidation::FedBans(Some(fed_id.to_string()))).await;
+        Ok(())
+    }
+
+    pub async fn remove_fed_ban(&self, fed_id: &str, user_id: i64) -> DbResult<()> {
+        self.store.remove_fed_ban(fed_id, user_id).await?;
+
+        // Invalidate cache
+        self.changed(Invalidation::FedBans(Some(fed_id.to_string()))).await;
+        Ok(())
+    }
+
+    pub async fn is_fed_banned(&self, group_id: i64, user_id: i64) -> DbResult<bool> {
+        let federation = match self.federation_of(group_id).await? {
+            Some(f) => f,
+            None => return Ok(false),
+        };
+
+        // Check cache first
+        if let Some(cached) = self.fban_cache.get(&federation.fed_id) {
+            return Ok(cached.contains(&user_id));
+        }
+
+        let users = match self.store.list_fed_bans(&federation.fed_id).await {
+            Ok(users) => Arc::new(users),
+            Err(e) => {
+                let stale = self.fban_cache.get_stale(&federation.fed_id);
+                return stale_or(stale, e, "ban federasi").map(|users| users.contains(&user_id));
+            }
+        };
+
+        let banned = users.contains(&user_id);
+
+        // Update cache
+        self.fban_cache.insert(federation.fed_id.clone(), users);
+
+        Ok(banned)
+    }
+
+    // Global ban atau ban federasi grup ini
+    pub async fn is_banned(&self, group_id: i64, user_id: i64) -> DbResult<bool> {
+        Ok(self.is_globally_banned(user_id).await? || self.is_fed_banned(group_id, user_id).await?)
+    }
+
+    pub async fn set_connection(&self, user_id: i64, group_id: i64) -> DbResult<()> {
+        self.store.set_connection(user_id, group_id).await
+    }
+
+    pub async fn clear_connection(&self, user_id: i64) -> DbResult<()> {
+        self.store.clear_connection(user_id).await
+    }
+
+    pub async fn get_connection(&self, user_id: i64) -> DbResult<Option<i64>> {
+        self.store.get_connection(user_id).await
+    }
+
+    pub async fn save_template(&self, template: ConfigTemplate) -> DbResult<()> {
+        self.store.save_template(template).await
+    }
+
+    pub async fn get_template(&self, owner_id: i64, name: &str) -> DbResult<Option<ConfigTemplate>> {
+        self.store.get_template(owner_id, name).await
+    }
+
+    pub async fn delete_template(&self, owner_id: i64, name: &str) -> DbResult<()> {
+        self.store.delete_template(owner_id, name).await
+    }
+
+    pub async fn list_templates(&self, owner_id: i64) -> DbResult<Vec<ConfigTemplate>> {
+        self.store.list_templates(owner_id).await
     }
 
     // Batch operations untuk performa yang lebih baik
-    pub async fn get_chat_data(&self, group_id: i64) -> (bool, Vec<String>, Vec<String>) {
-        tokio::join!(
-            self.is_enabled(group_id),
-            self.list_blacklist(group_id),
+    pub async fn get_chat_data(&self, group_id: i64) -> DbResult<(GroupSettings, Vec<String>, Vec<String>)> {
+        let (settings, blacklist, whitelist) = tokio::join!(
+            self.get_settings(group_id),
+            self.effective_blacklist(group_id),
             self.list_whitelist(group_id)
-        )
+        );
+        Ok((settings?, blacklist?, whitelist?))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_db() -> Database {
+        Database::with_store(Arc::new(MemoryStore::default()))
+    }
+
+    #[test]
+    fn normalize_keywords_trims_lowercases_and_dedupes() {
+        let keywords = vec!["  Promo   VCS ".to_string(), "promo vcs".to_string(), "   ".to_string(), "TMO".to_string()];
+        assert_eq!(normalize_keywords(keywords), vec!["promo vcs", "tmo"]);
+    }
+
+    #[tokio::test]
+    async fn keywords_are_stored_normalized() {
+        let db = memory_db();
+        assert!(db.add_blacklist(1, "  Open  VCS ".to_string()).await.unwrap());
+        assert!(!db.add_blacklist(1, "open vcs".to_string()).await.unwrap());
+        db.add_blacklist_many(1, vec!["B".to_string(), "b ".to_string(), "a".to_string()]).await.unwrap();
+
+        assert_eq!(db.list_blacklist(1).await.unwrap(), vec!["a", "b", "open vcs"]);
+        assert!(db.remove_blacklist(1, "OPEN VCS".to_string()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn reported_message_is_recorded_once_and_ham_exempts_similar_text() {
+        let db = memory_db();
+        assert!(db.record_example(1, 5, Some(10), 20, "rapat pengurus jam sembilan", false).await.unwrap());
+        // Laporan berulang untuk pesan yang sama tidak dicatat lagi
+        assert!(!db.record_example(1, 5, Some(10), 21, "rapat pengurus jam sembilan", false).await.unwrap());
+
+        assert!(db.is_ham_exempt(1, "Rapat pengurus jam sembilan!").await.unwrap());
+        assert!(!db.is_ham_exempt(1, "promo vcs murah").await.unwrap());
+        assert!(!db.is_ham_exempt(2, "rapat pengurus jam sembilan").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn effective_blacklist_merges_global_and_federation_source() {
+        let db = memory_db();
+        db.add_blacklist(1, "satu".to_string()).await.unwrap();
+        db.add_blacklist(2, "dua".to_string()).await.unwrap();
+        db.add_blacklist(3, "tiga".to_string()).await.unwrap();
+        db.add_global_keyword("global".to_string()).await.unwrap();
+
+        let federation = db.create_federation("fed".to_string(), 10, 2).await.unwrap();
+        for group in [1, 2, 3] {
+            db.join_federation(&federation.fed_id, group).await.unwrap();
+        }
+
+        let mut keywords = db.effective_blacklist(1).await.unwrap();
+        keywords.sort();
+        assert_eq!(keywords, vec!["global", "satu"]);
+
+        // Hanya blacklist grup sumber yang dibagikan, bukan semua anggota
+        db.set_fed_share_blacklist(&federation.fed_id, true, 2).await.unwrap();
+        let mut keywords = db.effective_blacklist(1).await.unwrap();
+        keywords.sort();
+        assert_eq!(keywords, vec!["dua", "global", "satu"]);
+
+        db.set_use_global_keywords(1, false).await.unwrap();
+        let mut keywords = db.effective_blacklist(1).await.unwrap();
+        keywords.sort();
+        assert_eq!(keywords, vec!["dua", "satu"]);
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+use teloxide::prelude::*;
+
+// Error dari lapisan penyimpanan
+#[derive(Debug)]
+pub enum DbError {
+    Mongo(mongodb::error::Error),
+    Sqlite(rusqlite::Error),
+    // Serialisasi data atau task backend yang gagal
+    Backend(String),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Mongo(e) => write!(f, "mongodb: {}", e),
+            DbError::Sqlite(e) => write!(f, "sqlite: {}", e),
+            DbError::Backend(e) => write!(f, "backend: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<mongodb::error::Error> for DbError {
+    fn from(e: mongodb::error::Error) -> Self {
+        DbError::Mongo(e)
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        DbError::Sqlite(e)
+    }
+}
+
+impl From<mongodb::bson::ser::Error> for DbError {
+    fn from(e: mongodb::bson::ser::Error) -> Self {
+        DbError::Backend(e.to_string())
+    }
+}
+
+impl From<mongodb::bson::de::Error> for DbError {
+    fn from(e: mongodb::bson::de::Error) -> Self {
+        DbError::Backend(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for DbError {
+    fn from(e: serde_json::Error) -> Self {
+        DbError::Backend(e.to_string())
+    }
+}
+
+pub type DbResult<T> = Result<T, DbError>;
+
+// Error handler command: request Telegram gagal atau database gagal.
+// Error database dibalas ke user, error Telegram diteruskan ke dispatcher.
+#[derive(Debug)]
+pub enum HandlerError {
+    Request(teloxide::RequestError),
+    Db(DbError),
+}
+
+impl From<teloxide::RequestError> for HandlerError {
+    fn from(e: teloxide::RequestError) -> Self {
+        HandlerError::Request(e)
+    }
+}
+
+impl From<DbError> for HandlerError {
+    fn from(e: DbError) -> Self {
+        HandlerError::Db(e)
+    }
+}
+
+pub type HandlerResult<T = ()> = Result<T, HandlerError>;
+
+// Pesan untuk user saat database tidak bisa diakses
+pub const DB_ERROR_REPLY: &str = "gagal mengakses database, perubahan tidak diterapkan. coba lagi nanti.";
+
+// Balas error database ke user; error Telegram tetap diteruskan ke dispatcher
+pub async fn reply_on_db_error(bot: &Bot, chat_id: ChatId, result: HandlerResult) -> ResponseResult<()> {
+    match result {
+        Ok(()) => Ok(()),
+        Err(HandlerError::Db(e)) => {
+            log::error!("Database error di chat {}: {}", chat_id, e);
+            bot.send_message(chat_id, DB_ERROR_REPLY).await?;
+            Ok(())
+        }
+        Err(HandlerError::Request(e)) => Err(e),
+    }
+}
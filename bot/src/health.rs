@@ -0,0 +1,251 @@
+use futures_util::stream::{self, BoxStream, StreamExt};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use teloxide::stop::StopToken;
+use teloxide::types::{AllowedUpdate, Update};
+use teloxide::update_listeners::{AsUpdateStream, UpdateListener};
+use crate::database::Database;
+use crate::metrics;
+
+// Heartbeat lebih lama dari ini berarti dispatcher macet
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+const STORAGE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+const DISPATCHER_STARTING: u8 = 0;
+const DISPATCHER_RUNNING: u8 = 1;
+const DISPATCHER_STOPPED: u8 = 2;
+
+const CACHE_SYNC_OFF: u8 = 0;
+const CACHE_SYNC_RUNNING: u8 = 1;
+const CACHE_SYNC_FAILED: u8 = 2;
+
+// Status proses untuk /healthz dan /readyz. Waktu disimpan sebagai milidetik
+// sejak START, 0 berarti belum pernah.
+static START: Lazy<Instant> = Lazy::new(Instant::now);
+static HEARTBEAT: AtomicU64 = AtomicU64::new(0);
+static LAST_UPDATE: AtomicU64 = AtomicU64::new(0);
+static DISPATCHER: AtomicU8 = AtomicU8::new(DISPATCHER_STARTING);
+static TELEGRAM_READY: AtomicBool = AtomicBool::new(false);
+static STORAGE_READY: AtomicBool = AtomicBool::new(false);
+static STORAGE_ERROR: Mutex<Option<String>> = Mutex::new(None);
+static CACHE_SYNC: AtomicU8 = AtomicU8::new(CACHE_SYNC_OFF);
+static CACHE_SYNC_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+fn now_millis() -> u64 {
+    // +1 supaya tidak pernah bernilai 0 (belum pernah)
+    START.elapsed().as_millis() as u64 + 1
+}
+
+fn age_secs(stamp: &AtomicU64) -> Option<f64> {
+    match stamp.load(Ordering::Relaxed) {
+        0 => None,
+        millis => Some(now_millis().saturating_sub(millis) as f64 / 1000.0),
+    }
+}
+
+// Dipanggil untuk setiap update yang masuk ke dispatcher
+pub fn mark_update() {
+    let now = now_millis();
+    LAST_UPDATE.store(now, Ordering::Relaxed);
+    HEARTBEAT.store(now, Ordering::Relaxed);
+}
+
+pub fn set_dispatcher_running(running: bool) {
+    let state = if running { DISPATCHER_RUNNING } else { DISPATCHER_STOPPED };
+    DISPATCHER.store(state, Ordering::Relaxed);
+}
+
+pub fn set_storage(result: Result<(), String>) {
+    STORAGE_READY.store(result.is_ok(), Ordering::Relaxed);
+    *STORAGE_ERROR.lock() = result.err();
+}
+
+// Status sinkronisasi cache antar instance (storage.cache_sync)
+pub fn set_cache_sync(result: Result<(), String>) {
+    let state = if result.is_ok() { CACHE_SYNC_RUNNING } else { CACHE_SYNC_FAILED };
+    CACHE_SYNC.store(state, Ordering::Relaxed);
+    *CACHE_SYNC_ERROR.lock() = result.err();
+}
+
+// Listener yang memperbarui heartbeat setiap kali dispatcher meminta update
+// berikutnya, juga saat grup sepi (tick idle). Kalau dispatcher macet karena
+// antrian worker penuh atau handler tidak pernah selesai, stream ini tidak
+// di-poll lagi, heartbeat berhenti, dan /healthz gagal.
+pub struct Heartbeat<L> {
+    inner: L,
+}
+
+pub fn heartbeat<L: UpdateListener>(listener: L) -> Heartbeat<L> {
+    Heartbeat { inner: listener }
+}
+
+impl<'a, L> AsUpdateStream<'a> for Heartbeat<L>
+where
+    L: UpdateListener + Send + 'a,
+    L::Err: Send + 'a,
+{
+    type StreamErr = L::Err;
+    type Stream = BoxStream<'a, Result<Update, L::Err>>;
+
+    fn as_stream(&'a mut self) -> Self::Stream {
+        let mut updates = self.inner.as_stream().boxed();
+        let mut idle = tokio::time::interval(HEARTBEAT_INTERVAL);
+        stream::poll_fn(move |cx| {
+            while idle.poll_tick(cx).is_ready() {
+                HEARTBEAT.store(now_millis(), Ordering::Relaxed);
+            }
+            updates.poll_next_unpin(cx)
+        })
+        .boxed()
+    }
+}
+
+impl<L> UpdateListener for Heartbeat<L>
+where
+    L: UpdateListener + Send + 'static,
+    L::Err: Send + 'static,
+{
+    type Err = L::Err;
+
+    fn stop_token(&mut self) -> StopToken {
+        self.inner.stop_token()
+    }
+
+    fn hint_allowed_updates(&mut self, hint: &mut dyn Iterator<Item = AllowedUpdate>) {
+        self.inner.hint_allowed_updates(hint)
+    }
+
+    fn timeout_hint(&self) -> Option<Duration> {
+        self.inner.timeout_hint()
+    }
+}
+
+// Ping storage secara berkala. Migrasi yang tertunda karena storage mati saat
+// start dijalankan begitu storage bisa dihubungi lagi.
+pub fn spawn_storage_check(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STORAGE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let was_ready = STORAGE_READY.load(Ordering::Relaxed);
+            match db.check_storage().await {
+                Ok(()) => {
+                    if !was_ready {
+                        log::info!("Storage kembali terhubung");
+                    }
+                    set_storage(Ok(()));
+                }
+                Err(e) => {
+                    if was_ready {
+                        log::error!("Storage tidak bisa dihubungi: {}", e);
+                    }
+                    set_storage(Err(e.to_string()));
+                }
+            }
+        }
+    });
+}
+
+// Tunggu sampai getMe berhasil, dengan backoff. Dispatcher panic jika getMe
+// gagal, jadi bot baru dijalankan setelah Telegram bisa dihubungi.
+pub async fn wait_for_telegram(bot: &Bot) {
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        match bot.get_me().await {
+            Ok(me) => {
+                log::info!("Terhubung ke Telegram sebagai @{}", me.username());
+                TELEGRAM_READY.store(true, Ordering::Relaxed);
+                return;
+            }
+            Err(e) => {
+                metrics::telegram_error(&e);
+                log::error!("getMe gagal, dicoba lagi dalam {}s: {}", backoff.as_secs(), e);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+// /healthz: proses hidup, dispatcher masih mengambil update, dan belum berhenti.
+// Selama masih menunggu storage/Telegram saat start, proses tetap dianggap hidup.
+pub fn liveness() -> (bool, Value) {
+    let heartbeat = age_secs(&HEARTBEAT);
+    let dispatcher = match DISPATCHER.load(Ordering::Relaxed) {
+        DISPATCHER_STARTING => "starting",
+        DISPATCHER_RUNNING => "running",
+        _ => "stopped",
+    };
+    let ok = dispatcher != "stopped" && heartbeat.is_none_or(|age| age < HEARTBEAT_TIMEOUT.as_secs_f64());
+    let body = json!({
+        "status": if ok { "ok" } else { "fail" },
+        "dispatcher": dispatcher,
+        "heartbeat_age_secs": heartbeat,
+        "last_update_age_secs": age_secs(&LAST_UPDATE),
+    });
+    (ok, body)
+}
+
+// /readyz: storage bisa dihubungi dan getMe sudah berhasil. Sinkronisasi cache
+// yang terputus hanya membuat status "degraded": cache tetap kedaluwarsa
+// sesuai TTL, jadi instance masih bisa melayani.
+pub fn readiness() -> (bool, Value) {
+    let storage = STORAGE_READY.load(Ordering::Relaxed);
+    let telegram = TELEGRAM_READY.load(Ordering::Relaxed);
+    let cache_sync = match CACHE_SYNC.load(Ordering::Relaxed) {
+        CACHE_SYNC_OFF => "off",
+        CACHE_SYNC_RUNNING => "ok",
+        _ => "fail",
+    };
+    let ok = storage && telegram;
+    let status = match (ok, cache_sync) {
+        (false, _) => "fail",
+        (true, "fail") => "degraded",
+        (true, _) => "ok",
+    };
+    let body = json!({
+        "status": status,
+        "storage": if storage { "ok" } else { "fail" },
+        "storage_error": *STORAGE_ERROR.lock(),
+        "telegram": if telegram { "ok" } else { "fail" },
+        "cache_sync": cache_sync,
+        "cache_sync_error": *CACHE_SYNC_ERROR.lock(),
+    });
+    (ok, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use teloxide::stop::mk_stop_token;
+    use teloxide::update_listeners::StatefulListener;
+
+    fn no_updates(_: &mut ()) -> stream::Pending<Result<Update, Infallible>> {
+        stream::pending()
+    }
+
+    fn stop_token(_: &mut ()) -> StopToken {
+        mk_stop_token().0
+    }
+
+    // Tanpa update sama sekali, heartbeat tetap diperbarui selama dispatcher
+    // masih meminta update berikutnya
+    #[tokio::test]
+    async fn idle_listener_keeps_heartbeat_fresh() {
+        let mut listener = heartbeat(StatefulListener::new((), no_updates, stop_token));
+        let mut updates = listener.as_stream();
+
+        HEARTBEAT.store(0, Ordering::Relaxed);
+        let next = tokio::time::timeout(Duration::from_millis(50), updates.next()).await;
+        assert!(next.is_err());
+        assert_ne!(HEARTBEAT.load(Ordering::Relaxed), 0);
+        assert!(liveness().0);
+    }
+}
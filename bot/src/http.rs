@@ -0,0 +1,59 @@
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use once_cell::sync::OnceCell;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use crate::config::HttpConfig;
+use crate::database::Database;
+use crate::{health, metrics};
+
+// Database diisi setelah storage berhasil dibuka. Server sudah jalan sebelum
+// itu supaya /healthz dan /readyz bisa menjawab selama start.
+type AppState = Arc<OnceCell<Database>>;
+
+async fn metrics_handler(State(db): State<AppState>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics::render(db.get()))
+}
+
+fn status((ok, body): (bool, Value)) -> (StatusCode, Json<Value>) {
+    let code = if ok { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (code, Json(body))
+}
+
+async fn healthz_handler() -> impl IntoResponse {
+    status(health::liveness())
+}
+
+async fn readyz_handler() -> impl IntoResponse {
+    status(health::readiness())
+}
+
+// Server HTTP internal untuk monitoring, terpisah dari port webhook supaya
+// tidak ikut terbuka ke publik lewat reverse proxy
+pub fn spawn(db: AppState, config: &HttpConfig) {
+    let address: SocketAddr = config.listen.parse().expect("http.listen tidak valid");
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler))
+        .with_state(db);
+
+    tokio::spawn(async move {
+        let server = match axum::Server::try_bind(&address) {
+            Ok(server) => server,
+            Err(e) => {
+                log::error!("Gagal membuka server HTTP di {}: {}", address, e);
+                return;
+            }
+        };
+
+        log::info!("Server monitoring di http://{} (/metrics, /healthz, /readyz)", address);
+        if let Err(e) = server.serve(app.into_make_service()).await {
+            log::error!("Server HTTP berhenti: {}", e);
+        }
+    });
+}
@@ -2,34 +2,132 @@ use teloxide::{prelude::*, dispatching::UpdateFilterExt};
 use teloxide::error_handlers::ErrorHandler;
 use futures_util::future::BoxFuture;
 use std::sync::Arc;
+use once_cell::sync::OnceCell;
 
 mod admin;
+mod admins;
+mod permissions;
 mod message;
 mod database;
 mod models;
+mod names;
+mod classifier;
+mod action;
+mod owner;
+mod settings;
+mod transfer;
+mod error;
+mod store;
+mod cache;
+mod config;
+mod webhook;
+mod metrics;
+mod http;
+mod health;
+mod queue;
+mod bot_api;
 
 use admin::{AdminCommand};
-use message::{cleanup_old_messages, handle_message_predictive}; // Added predictive handler
+use owner::OwnerCommand;
+use message::{spawn_cache_cleanup, handle_message_predictive}; // Added predictive handler
 use database::Database;
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 4)]
-async fn main() {
-    dotenv::dotenv().ok();
+// Runtime dibuat manual karena jumlah worker thread diambil dari konfigurasi
+fn main() {
+    // Konfigurasi dimuat sebelum .env masuk ke env proses, supaya env asli bisa
+    // dibedakan dari isi .env yang dibaca ulang saat /reload
+    let config = config::init();
+    dotenvy::dotenv().ok();
     pretty_env_logger::init();
+
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(config.bot.worker_threads)
+        .enable_all()
+        .build()
+        .expect("Gagal membuat runtime tokio")
+        .block_on(run());
+}
+
+async fn run() {
+    // `bot migrate`: terapkan migrasi skema lalu keluar
+    if std::env::args().nth(1).as_deref() == Some("migrate") {
+        match Database::migrate().await {
+            Ok(report) => {
+                if !report.applied.is_empty() {
+                    log::info!("Migrasi diterapkan: {}", report.applied.join(", "));
+                }
+                if !report.pending.is_empty() {
+                    log::error!(
+                        "Migrasi masih diklaim instance lain, belum diterapkan: {}",
+                        report.pending.join(", ")
+                    );
+                    std::process::exit(1);
+                }
+                if report.applied.is_empty() {
+                    log::info!("Tidak ada migrasi yang perlu dijalankan");
+                }
+            }
+            Err(e) => {
+                log::error!("Migrasi gagal: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     log::info!("🚀 Bot anti-gcast ultra-fast dimulai...");
 
     let bot = Bot::from_env();
+
+    // Server monitoring dijalankan sebelum storage dibuka supaya /healthz dan
+    // /readyz sudah bisa menjawab selama storage atau Telegram dicoba ulang
+    metrics::init();
+    let http_database = Arc::new(OnceCell::new());
+    let http_config = config::get().http.clone();
+    if http_config.enabled {
+        http::spawn(http_database.clone(), &http_config);
+    }
+
     let db = Database::init().await;
+    let _ = http_database.set(db.clone());
+    health::spawn_storage_check(db.clone());
 
     // Start background cleanup task
-    cleanup_old_messages().await;
+    spawn_cache_cleanup(db.clone());
+    classifier::spawn_pruning(db.clone());
+    config::spawn_watcher();
+
+    health::wait_for_telegram(&bot).await;
 
     // Clone untuk menghindari move issues
     let db_message = db.clone();
     let db_admin = db.clone();
     let db_predictive = db.clone(); // Clone untuk predictive handler
+    let db_members = db.clone();
+    let db_owner = db.clone();
+    let db_callback = db.clone();
+
+    let track_admins = |update: ChatMemberUpdated| async move {
+        admins::handle_member_update(&update);
+        Ok::<(), teloxide::RequestError>(())
+    };
 
     let handler = dptree::entry()
+        .inspect(health::mark_update)
+        .branch(
+            Update::filter_message()
+                .filter_command::<OwnerCommand>()
+                .endpoint(move |bot: Bot, msg: Message, cmd: OwnerCommand| {
+                    let db = db_owner.clone();
+                    async move {
+                        if let Err(e) = owner::handle_command(bot, db, msg, cmd).await {
+                            metrics::telegram_error(&e);
+                            log::warn!("Owner command error: {:?}", e);
+                        }
+                        Ok::<(), teloxide::RequestError>(())
+                    }
+                })
+        )
         .branch(
             Update::filter_message()
                 .filter_command::<AdminCommand>()
@@ -40,6 +138,7 @@ async fn main() {
                         match admin::handle_command(bot, db, msg, cmd).await {
                             Ok(_) => Ok::<(), teloxide::RequestError>(()),
                             Err(e) => {
+                                metrics::telegram_error(&e);
                                 log::warn!("Admin command error: {:?}", e);
                                 Ok(())
                             }
@@ -47,13 +146,56 @@ async fn main() {
                     }
                 })
         )
+        .branch(
+            Update::filter_callback_query()
+                .endpoint(move |bot: Bot, q: CallbackQuery| {
+                    let db = db_callback.clone();
+                    async move {
+                        if let Err(e) = settings::handle_callback(bot, db, q).await {
+                            metrics::telegram_error(&e);
+                            log::warn!("Callback query error: {:?}", e);
+                        }
+                        Ok::<(), teloxide::RequestError>(())
+                    }
+                })
+        )
+        // Perubahan admin memperbarui cache admin tanpa getChatAdministrators
+        .branch(Update::filter_chat_member().endpoint(track_admins))
+        .branch(Update::filter_my_chat_member().endpoint(track_admins))
+        .branch(
+            Update::filter_message()
+                .filter(|msg: Message| msg.new_chat_members().is_some())
+                .endpoint(move |bot: Bot, msg: Message| {
+                    let db = db_members.clone();
+                    async move {
+                        if let Err(e) = names::handle_new_members(bot, db, msg).await {
+                            metrics::telegram_error(&e);
+                            log::debug!("New member handling error: {:?}", e);
+                        }
+                        Ok::<(), teloxide::RequestError>(())
+                    }
+                })
+        )
         .branch(
             Update::filter_message()
                 .endpoint(move |bot: Bot, msg: Message| {
                     let db_msg = db_message.clone();
                     let db_pred = db_predictive.clone();
                     async move {
-                        // Jalankan kedua handler secara paralel untuk performa optimal
+                        let _timer = metrics::PIPELINE_SECONDS.start_timer();
+
+                        // Pengirim yang kena global ban tidak perlu masuk pipeline
+                        match message::enforce_bans(&bot, &db_msg, &msg).await {
+                            Ok(true) => return Ok(()),
+                            Ok(false) => {}
+                            Err(e) => {
+                                metrics::telegram_error(&e);
+                                log::debug!("Ban enforcement error: {:?}", e);
+                            }
+                        }
+
+                        // Pipeline deteksi hanya di handle_message; handler predictive
+                        // cuma mengisi cache untuk grup yang sangat aktif
                         let (result1, result2) = tokio::join!(
                             message::handle_message(bot.clone(), db_msg, msg.clone()),
                             handle_message_predictive(bot, db_pred, msg)
@@ -61,9 +203,11 @@ async fn main() {
 
                         // Handle errors dari kedua handler
                         if let Err(e) = result1 {
+                            metrics::telegram_error(&e);
                             log::debug!("Message handling error: {:?}", e);
                         }
                         if let Err(e) = result2 {
+                            metrics::telegram_error(&e);
                             log::debug!("Predictive handling error: {:?}", e);
                         }
 
@@ -73,12 +217,34 @@ async fn main() {
         );
 
     // Optimized dispatcher dengan custom error handler
-    Dispatcher::builder(bot, handler)
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
         .enable_ctrlc_handler()
         .error_handler(Arc::new(LoggingErrorHandler::new()))
-        .build()
-        .dispatch()
-        .await;
+        .build();
+
+    // Long polling (default) atau webhook, lihat bagian [webhook] di config
+    let webhook_config = config::get().webhook.clone();
+    if webhook_config.enabled {
+        let listener = match webhook::listener(bot, &webhook_config).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::error!("Mode webhook tidak bisa dijalankan: {}", e);
+                health::set_dispatcher_running(false);
+                std::process::exit(1);
+            }
+        };
+        health::set_dispatcher_running(true);
+        dispatcher
+            .dispatch_with_listener(health::heartbeat(listener), Arc::new(LoggingErrorHandler::new()))
+            .await;
+    } else {
+        let listener = webhook::polling(bot).await;
+        health::set_dispatcher_running(true);
+        dispatcher
+            .dispatch_with_listener(health::heartbeat(listener), Arc::new(LoggingErrorHandler::new()))
+            .await;
+    }
+    health::set_dispatcher_running(false);
 }
 
 // Custom error handler yang tidak memperlambat performa
@@ -1,35 +1,202 @@
 use teloxide::prelude::*;
+use teloxide::types::User;
 use crate::database::Database;
+use crate::models::GroupSettings;
+use crate::names;
+use crate::classifier;
 use regex::Regex;
-use dashmap::DashMap;
+use crate::cache::{BoundedCache, CacheStats};
+use crate::config;
+use crate::admins;
+use crate::metrics;
+use crate::queue;
 use once_cell::sync::Lazy;
-use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 // Ultra-fast concurrent storage untuk duplicate detection
-static LAST_MESSAGES: Lazy<Arc<DashMap<i64, String>>> = Lazy::new(|| Arc::new(DashMap::new()));
+static LAST_MESSAGES: Lazy<BoundedCache<i64, String>> = Lazy::new(|| {
+    let age = config::get().cache.message_age();
+    BoundedCache::new("last_messages", 10_000, age, age, |_, text| text.capacity())
+});
 
 // Pre-compiled regex patterns - kompilasi sekali saja
 static MENTION_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"@[\w\d_]{5,}").unwrap());
 static URL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"https?://\S+|t\.me/\S+|wa\.me/\S+|bit\.ly/\S+").unwrap());
 static EMOJI_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"[\u{1F600}-\u{1F64F}\u{2700}-\u{27BF}\u{1F680}-\u{1F6FF}\u{1F300}-\u{1F5FF}]").unwrap());
 
-// Suspicious keywords dalam static array untuk performa maksimal
-const SUSPICIOUS_KEYWORDS: [&str; 4] = ["tmo", "vcs", "vcan", "vcs-an"];
+// Normalisasi teks supaya semua detector (pesan maupun nama) melihat input yang sama
+pub fn normalize(text: &str) -> String {
+    text.trim().to_lowercase()
+}
+
+// Bentuk kanonik keyword yang disimpan: lowercase dengan spasi tunggal
+pub fn normalize_keyword(keyword: &str) -> String {
+    normalize(keyword).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn count_emoji(text: &str) -> usize {
+    EMOJI_RE.find_iter(text).count()
+}
+
+pub fn has_link_or_mention(text: &str) -> bool {
+    MENTION_RE.is_match(text) || URL_RE.is_match(text)
+}
+
+// Cek blacklist efektif (grup + global) terhadap teks yang sudah dinormalisasi
+pub fn matches_keywords(text: &str, blacklist: &[String]) -> bool {
+    blacklist.iter().any(|kw| text.contains(&kw.to_lowercase()))
+}
+
+// Catat grup dan tegakkan global/federation ban sebelum pipeline deteksi.
+// Mengembalikan true jika pengirim sudah dikeluarkan.
+pub async fn enforce_bans(bot: &Bot, db: &Database, msg: &Message) -> ResponseResult<bool> {
+    if !msg.chat.is_group() && !msg.chat.is_supergroup() {
+        return Ok(false);
+    }
+
+    let chat_id = msg.chat.id.0;
+    if let Err(e) = db.track_group(chat_id, msg.chat.title().unwrap_or_default()).await {
+        log::warn!("Gagal mencatat grup {}: {}", chat_id, e);
+    }
+
+    let user = match msg.from() {
+        Some(u) => u,
+        None => return Ok(false),
+    };
+
+    // Database gagal: pengirim dianggap tidak di-ban daripada salah menghukum
+    let banned = match db.is_enabled(chat_id).await {
+        Ok(true) => db.is_banned(chat_id, user.id.0 as i64).await,
+        Ok(false) => Ok(false),
+        Err(e) => Err(e),
+    };
+    match banned {
+        Ok(true) => {}
+        Ok(false) => return Ok(false),
+        Err(e) => {
+            log::warn!("Cek ban gagal di {}: {}", chat_id, e);
+            return Ok(false);
+        }
+    }
+
+    let (chat, user_id) = (msg.chat.id, user.id);
+    queue::delete(bot, chat, msg.id, "ban");
+    queue::run(bot, chat, "ban", move |bot| async move { bot.ban_chat_member(chat, user_id).await.map(drop) }).await?;
+    metrics::ACTIONS.with_label_values(&["ban_enforce"]).inc();
+    Ok(true)
+}
+
+// Detector pertama yang menandai pesan, atau None jika pesan bersih
+async fn detect(
+    db: &Database,
+    settings: &GroupSettings,
+    blacklist: &[String],
+    msg: &Message,
+    text: &str,
+    is_duplicate: bool,
+) -> Option<&'static str> {
+    let detectors = &settings.detectors;
+
+    if detectors.duplicate && is_duplicate {
+        return Some("duplicate");
+    }
+    if detectors.keywords && matches_keywords(text, blacklist) {
+        return Some("keywords");
+    }
+    if detectors.links && has_link_or_mention(text) {
+        return Some("links");
+    }
+    if detectors.emoji && count_emoji(text) as i64 > settings.emoji_threshold {
+        return Some("emoji");
+    }
+    if detectors.names
+        && msg.from().is_some_and(|u| names::is_suspicious_user(u, blacklist, settings.name_score_threshold))
+    {
+        return Some("names");
+    }
+    // Classifier paling mahal, jadi dicek terakhir
+    if detectors.classifier && classifier::is_spam(db, settings.group_id, text, settings.spam_threshold).await {
+        return Some("classifier");
+    }
+
+    None
+}
+
+// Jalur pesan tidak boleh berhenti karena database; cache lama sudah dipakai
+// di Database, jadi Err di sini berarti tidak ada data sama sekali untuk grup ini.
+async fn load_chat_data(db: &Database, chat_id: i64) -> Option<(GroupSettings, Vec<String>, Vec<String>)> {
+    match db.get_chat_data(chat_id).await {
+        Ok(data) => Some(data),
+        Err(e) => {
+            log::warn!("Lewati pesan di {}, data grup tidak tersedia: {}", chat_id, e);
+            None
+        }
+    }
+}
+
+async fn is_ham_exempt(db: &Database, chat_id: i64, text: &str) -> bool {
+    db.is_ham_exempt(chat_id, text).await.unwrap_or_else(|e| {
+        log::warn!("Cek ham gagal di {}: {}", chat_id, e);
+        false
+    })
+}
+
+// Laporkan tindakan ke log channel grup (jika diatur) dalam bahasa grup
+pub fn report_action(bot: &Bot, settings: &GroupSettings, user: Option<&User>, reason: &str) {
+    let Some(channel) = settings.log_channel else {
+        return;
+    };
+
+    let who = user.map_or_else(|| "?".to_string(), |u| format!("{} ({})", u.full_name(), u.id));
+    let group = settings.group_id;
+    let text = match (settings.language.as_str(), settings.is_observe()) {
+        ("en", false) => format!("🛡 Action taken against {} in {}: {}", who, group, reason),
+        ("en", true) => format!("👁 [observe] {} in {} flagged: {}", who, group, reason),
+        (_, false) => format!("🛡 {} ditindak di {}: {}", who, group, reason),
+        (_, true) => format!("👁 [observe] {} di {} ditandai: {}", who, group, reason),
+    };
+
+    queue::spawn(bot, ChatId(channel), "log", move |bot| {
+        let text = text.clone();
+        async move { bot.send_message(ChatId(channel), text).await.map(drop) }
+    });
+}
+
+fn act_on_detection(bot: Bot, settings: &GroupSettings, msg: &Message, detector: &'static str) {
+    report_action(&bot, settings, msg.from(), detector);
+
+    // Mode observe hanya mencatat apa yang akan dihapus
+    if settings.is_observe() {
+        log::info!("[observe] pesan {} di {} ditandai oleh {}", msg.id, msg.chat.id, detector);
+        return;
+    }
+
+    // Silent deletion lewat antrian: dibatasi rate, dicoba ulang, dan
+    // digabung dengan hapus lain di chat yang sama
+    queue::delete(&bot, msg.chat.id, msg.id, detector);
+}
 
 pub async fn handle_message(bot: Bot, db: Database, msg: Message) -> ResponseResult<()> {
     let chat_id = msg.chat.id.0;
-    let message_id = msg.id;
 
     // Super early return untuk non-text messages
     let text = match msg.text() {
-        Some(t) if !t.trim().is_empty() => t.to_lowercase(),
+        Some(t) if !t.trim().is_empty() => normalize(t),
         _ => return Ok(()),
     };
+    metrics::MESSAGES_SCANNED.inc();
 
     // Batch database operations dalam satu call
-    let (is_enabled, blacklist, whitelist) = db.get_chat_data(chat_id).await;
+    let Some((settings, blacklist, whitelist)) = load_chat_data(&db, chat_id).await else {
+        return Ok(());
+    };
 
-    if !is_enabled {
+    if !settings.is_enabled() {
+        return Ok(());
+    }
+
+    // Admin (termasuk admin anonim) tidak diperiksa
+    if admins::is_sender_admin(&bot, &msg).await {
         return Ok(());
     }
 
@@ -38,10 +205,15 @@ pub async fn handle_message(bot: Bot, db: Database, msg: Message) -> ResponseRes
         return Ok(());
     }
 
-    // Lightning-fast duplicate detection menggunakan DashMap
+    // Pesan yang mirip false positive (/ham) tidak diproses
+    if is_ham_exempt(&db, chat_id, &text).await {
+        return Ok(());
+    }
+
+    // Lightning-fast duplicate detection menggunakan cache in-memory
     let is_duplicate = {
         match LAST_MESSAGES.get(&chat_id) {
-            Some(prev) if prev.value() == &text => true,
+            Some(prev) if prev == text => true,
             _ => {
                 LAST_MESSAGES.insert(chat_id, text.clone());
                 false
@@ -49,144 +221,95 @@ pub async fn handle_message(bot: Bot, db: Database, msg: Message) -> ResponseRes
         }
     };
 
-    // Batch semua checks dalam satu pipeline untuk maksimal efisiensi
-    let should_delete = is_duplicate
-        || SUSPICIOUS_KEYWORDS.iter().any(|&kw| text.contains(kw))
-        || MENTION_RE.is_match(&text)
-        || URL_RE.is_match(&text)
-        || EMOJI_RE.find_iter(&text).count() > 5
-        || blacklist.iter().any(|kw| text.contains(&kw.to_lowercase()));
-
-    if should_delete {
-        // Ultimate silent deletion - fire-and-forget dengan minimal overhead
-        let bot_clone = bot.clone();
-        let chat_id_clone = msg.chat.id;
-        tokio::spawn(async move {
-            let _ = bot_clone.delete_message(chat_id_clone, message_id).await;
-        });
+    if let Some(detector) = detect(&db, &settings, &blacklist, &msg, &text, is_duplicate).await {
+        act_on_detection(bot, &settings, &msg, detector);
     }
 
     Ok(())
 }
 
-// Advanced version dengan predictive caching untuk grup yang sangat aktif
-use std::time::{Duration, Instant};
-
+// Frekuensi pesan per grup untuk predictive caching
 #[derive(Clone)]
 struct MessageStats {
     count: u32,
     last_message: Instant,
 }
 
-static MESSAGE_STATS: Lazy<Arc<DashMap<i64, MessageStats>>> = Lazy::new(|| Arc::new(DashMap::new()));
+static MESSAGE_STATS: Lazy<BoundedCache<i64, MessageStats>> = Lazy::new(|| {
+    let age = config::get().cache.message_age();
+    BoundedCache::new("message_stats", 10_000, age, age, |_, _| 0)
+});
 
+// Predictive caching untuk grup yang sangat aktif: data grup dan daftar admin
+// dimuat ke cache lebih dulu supaya pesan berikutnya tidak menunggu database
+// atau Telegram. Deteksi dan tindakan hanya dijalankan handle_message, supaya
+// setiap pesan diproses sekali.
 pub async fn handle_message_predictive(bot: Bot, db: Database, msg: Message) -> ResponseResult<()> {
     let chat_id = msg.chat.id.0;
-    let message_id = msg.id;
-
-    let text = match msg.text() {
-        Some(t) if !t.trim().is_empty() => t.to_lowercase(),
-        _ => return Ok(()),
-    };
+    if msg.text().is_none_or(|t| t.trim().is_empty()) {
+        return Ok(());
+    }
 
     // Track message frequency untuk predictive caching
     let now = Instant::now();
     let is_high_traffic = {
-        match MESSAGE_STATS.get_mut(&chat_id) {
-            Some(mut stats) => {
-                stats.count += 1;
-                if stats.last_message.elapsed() < Duration::from_secs(1) {
-                    stats.count > 10 // High traffic jika >10 msg/detik
-                } else {
-                    stats.count = 1;
-                    stats.last_message = now;
-                    false
-                }
-            }
-            None => {
-                MESSAGE_STATS.insert(chat_id, MessageStats {
-                    count: 1,
-                    last_message: now,
-                });
-                false
-            }
-        }
-    };
-
-    // Gunakan strategi berbeda untuk high-traffic vs normal chat
-    let (is_enabled, blacklist, whitelist) = if is_high_traffic {
-        // Untuk high-traffic, prioritaskan cache
-        db.get_chat_data(chat_id).await
-    } else {
-        // Untuk normal traffic, batch query biasa
-        tokio::join!(
-            db.is_enabled(chat_id),
-            db.list_blacklist(chat_id),
-            db.list_whitelist(chat_id)
-        )
+        let mut stats = MESSAGE_STATS.get(&chat_id).unwrap_or(MessageStats {
+            count: 0,
+            last_message: now,
+        });
+        stats.count += 1;
+        let high = if stats.last_message.elapsed() < Duration::from_secs(1) {
+            stats.count > config::get().detection.high_traffic_per_sec
+        } else {
+            stats.count = 1;
+            stats.last_message = now;
+            false
+        };
+        MESSAGE_STATS.insert(chat_id, stats);
+        high
     };
-
-    if !is_enabled {
+    if !is_high_traffic {
         return Ok(());
     }
 
-    // Pre-compute lowercase keywords untuk avoid repeated operations
-    let whitelist_lower: Vec<String> = whitelist.iter().map(|kw| kw.to_lowercase()).collect();
-    let blacklist_lower: Vec<String> = blacklist.iter().map(|kw| kw.to_lowercase()).collect();
-
-    // Ultra-fast whitelist check
-    if whitelist_lower.iter().any(|kw| text.contains(kw)) {
-        return Ok(());
-    }
-
-    // Optimized duplicate detection
-    let is_duplicate = LAST_MESSAGES
-        .get(&chat_id)
-        .map_or(false, |prev| prev.value() == &text);
-
-    if !is_duplicate {
-        LAST_MESSAGES.insert(chat_id, text.clone());
+    let (data, admins) = tokio::join!(db.get_chat_data(chat_id), admins::get(&bot, chat_id));
+    if let Err(e) = data {
+        log::debug!("Gagal memuat data grup {} ke cache: {}", chat_id, e);
     }
-
-    // Parallel regex checks untuk maximum speed
-    let (has_mention, has_url, emoji_count) = tokio::join!(
-        async { MENTION_RE.is_match(&text) },
-        async { URL_RE.is_match(&text) },
-        async { EMOJI_RE.find_iter(&text).count() }
-    );
-
-    // Kombinasi semua checks
-    let should_delete = is_duplicate
-        || SUSPICIOUS_KEYWORDS.iter().any(|&kw| text.contains(kw))
-        || has_mention
-        || has_url
-        || emoji_count > 5
-        || blacklist_lower.iter().any(|kw| text.contains(kw));
-
-    if should_delete {
-        // Absolute silent deletion - zero latency
-        tokio::task::spawn(async move {
-            let _ = bot.delete_message(msg.chat.id, message_id).await;
-        });
-    }
-
+    admins?;
     Ok(())
 }
 
-// Memory management untuk long-running bots
-pub async fn cleanup_old_messages() {
-    tokio::spawn(async {
-        let mut interval = tokio::time::interval(Duration::from_secs(3600)); // Cleanup setiap jam
+// Memory management untuk long-running bots: buang entry kadaluarsa dari
+// semua cache in-memory secara berkala
+pub fn spawn_cache_cleanup(db: Database) {
+    tokio::spawn(async move {
+        let mut config_rx = config::subscribe();
         loop {
-            interval.tick().await;
-
-            // Clean up old message cache (keep last 1000 per chat)
-            for _entry in LAST_MESSAGES.iter_mut() {
-                // Implement LRU-like cleanup if needed
+            let interval = config::get().cache.cleanup_interval();
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                changed = config_rx.changed() => {
+                    if changed.is_ok() {
+                        // Umur cache bisa diubah tanpa restart
+                        let cache = config_rx.borrow_and_update().cache.clone();
+                        db.set_cache_age(cache.ttl(), cache.max_age());
+                        LAST_MESSAGES.set_age(cache.message_age(), cache.message_age());
+                        MESSAGE_STATS.set_age(cache.message_age(), cache.message_age());
+                        admins::set_ttl(cache.admin_ttl());
+                    }
+                    continue;
+                }
             }
 
-            // Clean up old stats
-            MESSAGE_STATS.retain(|_, stats| stats.last_message.elapsed() < Duration::from_secs(3600));
+            let evicted = LAST_MESSAGES.evict_expired() + MESSAGE_STATS.evict_expired() + admins::evict_expired() + db.evict_expired();
+            if evicted > 0 {
+                log::debug!("cache cleanup: {} entry dibuang", evicted);
+            }
         }
     });
-}
\ No newline at end of file
+}
+
+pub fn cache_stats() -> Vec<CacheStats> {
+    vec![LAST_MESSAGES.stats(), MESSAGE_STATS.stats()]
+}
@@ -0,0 +1,135 @@
+use mongodb::event::command::{CommandEventHandler, CommandFailedEvent, CommandSucceededEvent};
+use once_cell::sync::Lazy;
+use prometheus::core::Collector;
+use prometheus::{Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use teloxide::RequestError;
+use crate::admins;
+use crate::database::Database;
+use crate::message;
+
+static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+fn register<C: Collector + Clone + 'static>(collector: C) -> C {
+    REGISTRY.register(Box::new(collector.clone())).expect("Nama metrik ganda");
+    collector
+}
+
+fn counter(name: &str, help: &str) -> IntCounter {
+    register(IntCounter::new(name, help).expect("Metrik tidak valid"))
+}
+
+fn counter_vec(name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    register(IntCounterVec::new(Opts::new(name, help), labels).expect("Metrik tidak valid"))
+}
+
+fn gauge_vec(name: &str, help: &str, labels: &[&str]) -> IntGaugeVec {
+    register(IntGaugeVec::new(Opts::new(name, help), labels).expect("Metrik tidak valid"))
+}
+
+pub static MESSAGES_SCANNED: Lazy<IntCounter> =
+    Lazy::new(|| counter("antigcast_messages_scanned_total", "Pesan teks yang masuk pipeline deteksi"));
+
+pub static MESSAGES_DELETED: Lazy<IntCounterVec> = Lazy::new(|| {
+    counter_vec("antigcast_messages_deleted_total", "Pesan yang berhasil dihapus, per detector", &["detector"])
+});
+
+pub static ACTIONS: Lazy<IntCounterVec> =
+    Lazy::new(|| counter_vec("antigcast_actions_total", "Tindakan terhadap user, per jenis", &["action"]));
+
+pub static TELEGRAM_ERRORS: Lazy<IntCounterVec> =
+    Lazy::new(|| counter_vec("antigcast_telegram_errors_total", "Error dari Telegram Bot API, per jenis", &["kind"]));
+
+pub static ACTION_QUEUE_PENDING: Lazy<IntGauge> = Lazy::new(|| {
+    register(IntGauge::new("antigcast_action_queue_pending", "Aksi Telegram yang menunggu di antrian").expect("Metrik tidak valid"))
+});
+
+pub static ACTION_RETRIES: Lazy<IntCounterVec> = Lazy::new(|| {
+    counter_vec("antigcast_action_retries_total", "Request Telegram yang dicoba ulang, per alasan", &["reason"])
+});
+
+pub static CACHE_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    counter_vec("antigcast_cache_requests_total", "Pembacaan cache in-memory, per cache dan hasil (hit/miss)", &["cache", "result"])
+});
+
+static CACHE_ENTRIES: Lazy<IntGaugeVec> =
+    Lazy::new(|| gauge_vec("antigcast_cache_entries", "Jumlah entry per cache", &["cache"]));
+
+static CACHE_BYTES: Lazy<IntGaugeVec> =
+    Lazy::new(|| gauge_vec("antigcast_cache_bytes", "Perkiraan ukuran data per cache", &["cache"]));
+
+static MONGO_COMMAND_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    let opts = HistogramOpts::new("antigcast_mongo_command_seconds", "Latensi command MongoDB")
+        .buckets(vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]);
+    register(HistogramVec::new(opts, &["command"]).expect("Metrik tidak valid"))
+});
+
+static MONGO_COMMAND_ERRORS: Lazy<IntCounterVec> =
+    Lazy::new(|| counter_vec("antigcast_mongo_command_errors_total", "Command MongoDB yang gagal", &["command"]));
+
+pub static PIPELINE_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    let opts = HistogramOpts::new("antigcast_pipeline_seconds", "Waktu proses satu pesan grup, termasuk cek ban")
+        .buckets(vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]);
+    register(Histogram::with_opts(opts).expect("Metrik tidak valid"))
+});
+
+// Daftarkan semua metrik di awal supaya muncul di /metrics walau masih nol
+pub fn init() {
+    Lazy::force(&MESSAGES_SCANNED);
+    Lazy::force(&MESSAGES_DELETED);
+    Lazy::force(&ACTIONS);
+    Lazy::force(&TELEGRAM_ERRORS);
+    Lazy::force(&ACTION_QUEUE_PENDING);
+    Lazy::force(&ACTION_RETRIES);
+    Lazy::force(&CACHE_REQUESTS);
+    Lazy::force(&CACHE_ENTRIES);
+    Lazy::force(&CACHE_BYTES);
+    Lazy::force(&MONGO_COMMAND_SECONDS);
+    Lazy::force(&MONGO_COMMAND_ERRORS);
+    Lazy::force(&PIPELINE_SECONDS);
+}
+
+pub fn telegram_error(error: &RequestError) {
+    let kind = match error {
+        RequestError::Api(_) => "api",
+        RequestError::MigrateToChatId(_) => "migrate",
+        RequestError::RetryAfter(_) => "retry_after",
+        RequestError::Network(_) => "network",
+        RequestError::InvalidJson { .. } => "invalid_json",
+        RequestError::Io(_) => "io",
+    };
+    TELEGRAM_ERRORS.with_label_values(&[kind]).inc();
+}
+
+// Dipasang di ClientOptions MongoStore untuk mengukur setiap command
+pub struct MongoMetrics;
+
+impl CommandEventHandler for MongoMetrics {
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        MONGO_COMMAND_SECONDS
+            .with_label_values(&[&event.command_name])
+            .observe(event.duration.as_secs_f64());
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        MONGO_COMMAND_SECONDS
+            .with_label_values(&[&event.command_name])
+            .observe(event.duration.as_secs_f64());
+        MONGO_COMMAND_ERRORS.with_label_values(&[&event.command_name]).inc();
+    }
+}
+
+// Format teks Prometheus. Ukuran cache dihitung saat scrape; cache database
+// belum ada selama storage masih dicoba dibuka saat start.
+pub fn render(db: Option<&Database>) -> String {
+    let database_stats = db.map(Database::cache_stats).unwrap_or_default();
+    for stats in database_stats.into_iter().chain(message::cache_stats()).chain([admins::cache_stats()]) {
+        CACHE_ENTRIES.with_label_values(&[stats.name]).set(stats.entries as i64);
+        CACHE_BYTES.with_label_values(&[stats.name]).set(stats.bytes as i64);
+    }
+
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer) {
+        log::warn!("Gagal encode metrik: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
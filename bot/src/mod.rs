@@ -1,4 +1,22 @@
 pub mod admin;
 pub mod message;
 pub mod database;
-pub mod models;
\ No newline at end of file
+pub mod models;
+pub mod names;
+pub mod classifier;
+pub mod action;
+pub mod owner;
+pub mod settings;
+pub mod transfer;
+pub mod error;
+pub mod store;
+pub mod cache;
+pub mod config;
+pub mod webhook;
+pub mod metrics;
+pub mod http;
+pub mod health;
+pub mod queue;
+pub mod bot_api;
+pub mod admins;
+pub mod permissions;
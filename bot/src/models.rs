@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use mongodb::bson::oid::ObjectId;
+use std::ops::RangeInclusive;
+use mongodb::bson::{oid::ObjectId, Bson, DateTime, Document};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BlacklistItem {
@@ -17,10 +18,433 @@ pub struct WhitelistItem {
     pub keyword: String,
 }
 
+// Keyword global yang dikelola owner bot, berlaku untuk semua grup
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GlobalKeyword {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub keyword: String,
+}
+
+// Versi skema dokumen settings. Naikkan dan tambahkan langkah di
+// upgrade_settings() setiap kali bentuk dokumen berubah.
+pub const SETTINGS_VERSION: i32 = 1;
+
+// Bahasa yang didukung untuk pesan log grup
+pub const LANGUAGES: [&str; 2] = ["id", "en"];
+
+// Batas strike_limit, dipakai /setstrikes dan tombol panel
+pub const STRIKE_LIMITS: RangeInclusive<i64> = 1..=100;
+
+// Batas threshold dari tombol panel dan file import
+pub const EMOJI_THRESHOLDS: RangeInclusive<i64> = 1..=20;
+pub const NAME_SCORE_THRESHOLDS: RangeInclusive<i64> = 1..=10;
+pub const SPAM_THRESHOLDS: RangeInclusive<f64> = 0.5..=0.99;
+
+// Seluruh konfigurasi satu grup dalam satu dokumen. Setiap field baru wajib
+// punya default serde supaya dokumen lama tetap bisa dibaca.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GroupSettings {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub group_id: i64,
-    pub enabled: bool,
+    // Dokumen tanpa field ini berasal dari sebelum skema diberi versi
+    #[serde(default)]
+    pub version: i32,
+    #[serde(default)]
+    pub mode: GroupMode,
+    #[serde(default)]
+    pub action: ActionPolicy,
+    #[serde(default = "default_strike_limit")]
+    pub strike_limit: i64,
+    // Grup yang memang membahas topik tersebut bisa opt-out dari keyword global
+    #[serde(default = "default_true")]
+    pub use_global_keywords: bool,
+    #[serde(default)]
+    pub detectors: DetectorToggles,
+    #[serde(default = "default_emoji_threshold")]
+    pub emoji_threshold: i64,
+    #[serde(default = "default_name_score_threshold")]
+    pub name_score_threshold: i64,
+    #[serde(default = "default_spam_threshold")]
+    pub spam_threshold: f64,
+    #[serde(default = "default_language")]
+    pub language: String,
+    // Chat/channel tujuan laporan setiap pesan yang ditindak
+    #[serde(default)]
+    pub log_channel: Option<i64>,
+    // Moderator bot: user bukan admin Telegram yang boleh memakai command moderasi
+    #[serde(default)]
+    pub moderators: Vec<i64>,
+}
+
+fn default_strike_limit() -> i64 {
+    3
+}
+
+fn default_emoji_threshold() -> i64 {
+    crate::config::get().detection.emoji_threshold
+}
+
+fn default_name_score_threshold() -> i64 {
+    3
+}
+
+fn default_spam_threshold() -> f64 {
+    0.95
+}
+
+fn default_language() -> String {
+    "id".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl GroupSettings {
+    // Default untuk grup yang belum punya dokumen settings
+    pub fn new(group_id: i64) -> Self {
+        Self {
+            id: None,
+            group_id,
+            version: SETTINGS_VERSION,
+            mode: GroupMode::default(),
+            action: ActionPolicy::default(),
+            strike_limit: default_strike_limit(),
+            use_global_keywords: true,
+            detectors: DetectorToggles::default(),
+            emoji_threshold: default_emoji_threshold(),
+            name_score_threshold: default_name_score_threshold(),
+            spam_threshold: default_spam_threshold(),
+            language: default_language(),
+            log_channel: None,
+            moderators: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.mode != GroupMode::Off
+    }
+
+    pub fn is_observe(&self) -> bool {
+        self.mode == GroupMode::Observe
+    }
+}
+
+// Upgrade dokumen settings lama ke SETTINGS_VERSION di tempat. Mengembalikan
+// true jika dokumen berubah sehingga perlu ditulis ulang ke storage.
+pub fn upgrade_settings(doc: &mut Document) -> bool {
+    let version = match doc.get("version") {
+        Some(Bson::Int32(v)) => *v as i64,
+        Some(Bson::Int64(v)) => *v,
+        _ => 0,
+    };
+    if version >= SETTINGS_VERSION as i64 {
+        return false;
+    }
+
+    // v0 -> v1: `enabled` + `observe` digabung menjadi `mode`. Dokumen lama
+    // yang sudah sempat diberi `mode` lewat update parsial dipertahankan.
+    if version < 1 {
+        if !doc.contains_key("mode") {
+            let enabled = doc.get_bool("enabled").unwrap_or(false);
+            let observe = doc.get_bool("observe").unwrap_or(false);
+            let mode = match (enabled, observe) {
+                (false, _) => GroupMode::Off,
+                (true, false) => GroupMode::On,
+                (true, true) => GroupMode::Observe,
+            };
+            doc.insert("mode", mode.as_str());
+        }
+        doc.remove("enabled");
+        doc.remove("observe");
+    }
+
+    doc.insert("version", SETTINGS_VERSION);
+    true
+}
+
+// Status bot di grup. Observe: deteksi tetap jalan dan dicatat, tapi pesan
+// tidak dihapus.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupMode {
+    #[default]
+    Off,
+    On,
+    Observe,
+}
+
+impl GroupMode {
+    pub const ALL: [GroupMode; 3] = [Self::Off, Self::On, Self::Observe];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::On => "on",
+            Self::Observe => "observe",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.as_str() == s.trim().to_lowercase())
+    }
+}
+
+// Detector yang bisa dimatikan per grup. Field disimpan satu per satu
+// ("detectors.<nama>"), jadi setiap field butuh default sendiri.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DetectorToggles {
+    #[serde(default = "default_true")]
+    pub duplicate: bool,
+    #[serde(default = "default_true")]
+    pub keywords: bool,
+    #[serde(default = "default_true")]
+    pub links: bool,
+    #[serde(default = "default_true")]
+    pub emoji: bool,
+    #[serde(default = "default_true")]
+    pub names: bool,
+    #[serde(default = "default_true")]
+    pub classifier: bool,
+}
+
+impl Default for DetectorToggles {
+    fn default() -> Self {
+        Self {
+            duplicate: true,
+            keywords: true,
+            links: true,
+            emoji: true,
+            names: true,
+            classifier: true,
+        }
+    }
+}
+
+impl DetectorToggles {
+    pub const NAMES: [&'static str; 6] = ["duplicate", "keywords", "links", "emoji", "names", "classifier"];
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "duplicate" => Some(self.duplicate),
+            "keywords" => Some(self.keywords),
+            "links" => Some(self.links),
+            "emoji" => Some(self.emoji),
+            "names" => Some(self.names),
+            "classifier" => Some(self.classifier),
+            _ => None,
+        }
+    }
+}
+
+// Hukuman untuk pengirim spam setelah strike mencapai batas
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionPolicy {
+    #[default]
+    Delete,
+    Mute,
+    Kick,
+    Ban,
+}
+
+impl ActionPolicy {
+    pub const ALL: [ActionPolicy; 4] = [Self::Delete, Self::Mute, Self::Kick, Self::Ban];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delete => "delete",
+            Self::Mute => "mute",
+            Self::Kick => "kick",
+            Self::Ban => "ban",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.as_str() == s.trim().to_lowercase())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StrikeRecord {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub group_id: i64,
+    pub user_id: i64,
+    #[serde(default)]
+    pub count: i64,
+}
+
+// Contoh spam/ham dari feedback admin, disimpan untuk audit dan retraining
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SpamExample {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub group_id: i64,
+    pub sender_id: Option<i64>,
+    // Pesan yang dilaporkan; satu pesan hanya dilatih sekali. Kosong untuk
+    // contoh dari sebelum field ini ada.
+    #[serde(default)]
+    pub message_id: Option<i32>,
+    pub reported_by: i64,
+    pub text: String,
+    pub is_spam: bool,
+    pub created_at: DateTime,
+}
+
+
+// Statistik token naive Bayes per scope (group_id, atau 0 untuk model global)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenStat {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub scope: i64,
+    pub token: String,
+    #[serde(default)]
+    pub spam: i64,
+    #[serde(default)]
+    pub ham: i64,
+    // Terakhir dilatih, untuk membuang token langka yang sudah lama
+    #[serde(default)]
+    pub updated_at: Option<DateTime>,
+}
+
+// Jumlah contoh spam/ham yang sudah dilatih per scope
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClassifierTotals {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub scope: i64,
+    #[serde(default)]
+    pub spam: i64,
+    #[serde(default)]
+    pub ham: i64,
+}
+
+
+// Grup yang pernah dilihat bot, untuk /groups dan /broadcast
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KnownGroup {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub group_id: i64,
+    pub title: String,
+    pub updated_at: DateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GlobalBan {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: i64,
+    pub reason: String,
+    pub banned_by: i64,
+    pub created_at: DateTime,
+}
+
+// Federasi: sekumpulan grup yang berbagi daftar ban (dan opsional blacklist)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Federation {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub fed_id: String,
+    pub name: String,
+    pub owner_id: i64,
+    #[serde(default)]
+    pub groups: Vec<i64>,
+    #[serde(default)]
+    pub share_blacklist: bool,
+    // Grup yang blacklist-nya dibagikan saat share_blacklist aktif. Diisi grup
+    // pembuat federasi, atau grup tempat owner menjalankan /fedbl on.
+    #[serde(default)]
+    pub blacklist_source: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FedBan {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub fed_id: String,
+    pub user_id: i64,
+    pub reason: String,
+    pub banned_by: i64,
+    pub created_at: DateTime,
+}
+
+// Grup yang sedang dikelola user dari chat pribadi
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Connection {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: i64,
+    pub group_id: i64,
+}
+
+// Isi file export/import dan template: blacklist, whitelist, dan settings grup
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GroupSnapshot {
+    #[serde(default)]
+    pub blacklist: Vec<String>,
+    #[serde(default)]
+    pub whitelist: Vec<String>,
+    // Disimpan sebagai JSON bebas supaya file buatan tangan tidak wajib lengkap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<serde_json::Value>,
+}
+
+// Preset bernama yang bisa diterapkan ke grup mana pun
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigTemplate {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub owner_id: i64,
+    pub snapshot: GroupSnapshot,
+    pub updated_at: DateTime,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    fn upgrade(mut document: Document) -> (bool, Document) {
+        let upgraded = upgrade_settings(&mut document);
+        (upgraded, document)
+    }
+
+    #[test]
+    fn upgrade_settings_maps_v0_flags_to_mode() {
+        let cases = [
+            (doc! { "group_id": 1_i64 }, GroupMode::Off),
+            (doc! { "group_id": 1_i64, "enabled": true }, GroupMode::On),
+            (doc! { "group_id": 1_i64, "enabled": true, "observe": true }, GroupMode::Observe),
+            (doc! { "group_id": 1_i64, "enabled": false, "observe": true }, GroupMode::Off),
+        ];
+        for (document, expected) in cases {
+            let (upgraded, document) = upgrade(document);
+            assert!(upgraded);
+            assert_eq!(document.get_str("mode").unwrap(), expected.as_str());
+            assert_eq!(document.get_i32("version").unwrap(), SETTINGS_VERSION);
+            assert!(!document.contains_key("enabled"));
+            assert!(!document.contains_key("observe"));
+
+            let settings: GroupSettings = mongodb::bson::from_document(document).unwrap();
+            assert_eq!(settings.mode, expected);
+        }
+    }
+
+    #[test]
+    fn upgrade_settings_keeps_mode_from_partial_update() {
+        let (_, document) = upgrade(doc! { "group_id": 1_i64, "enabled": false, "mode": "observe" });
+        assert_eq!(document.get_str("mode").unwrap(), "observe");
+    }
+
+    #[test]
+    fn upgrade_settings_skips_current_version() {
+        let (upgraded, _) = upgrade(doc! { "group_id": 1_i64, "version": SETTINGS_VERSION, "mode": "on" });
+        assert!(!upgraded);
+    }
 }
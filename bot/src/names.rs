@@ -0,0 +1,179 @@
+use teloxide::prelude::*;
+use teloxide::types::User;
+use crate::database::Database;
+use crate::metrics;
+use crate::queue;
+use crate::message::{self, count_emoji, has_link_or_mention, matches_keywords, normalize};
+use regex::Regex;
+use once_cell::sync::Lazy;
+
+// Username akun gcast biasanya berakhiran deretan angka acak (mis. "promo83749201").
+// Empat angka saja sering berupa tahun lahir ("johnny1990"), jadi minimal lima.
+static DIGIT_USERNAME_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d{5,}$").unwrap());
+
+// Nama jauh lebih pendek dari pesan, jadi batas emojinya juga lebih rendah
+const NAME_EMOJI_THRESHOLD: usize = 2;
+
+fn full_name(user: &User) -> String {
+    match &user.last_name {
+        Some(last) => format!("{} {}", user.first_name, last),
+        None => user.first_name.clone(),
+    }
+}
+
+// Nama "PROMO VCS 24JAM": semua huruf kapital dan cukup panjang
+fn is_shouting(name: &str) -> bool {
+    let letters: Vec<char> = name.chars().filter(|c| c.is_alphabetic()).collect();
+    letters.len() >= 6 && letters.iter().all(|c| c.is_uppercase())
+}
+
+// Skor heuristik untuk nama depan/belakang + username pengirim. Keyword,
+// link, dan nama penuh emoji masing-masing cukup untuk threshold bawaan (3);
+// username berangka acak dan huruf kapital hanya menambah skor, karena juga
+// dimiliki banyak akun biasa. Threshold per grup yang menentukan batasnya.
+pub fn score_name(user: &User, blacklist: &[String]) -> i64 {
+    let raw_name = full_name(user);
+    let name = normalize(&raw_name);
+    let username = user.username.as_deref().map(normalize).unwrap_or_default();
+
+    let mut score = 0;
+    if matches_keywords(&name, blacklist) || (!username.is_empty() && matches_keywords(&username, blacklist)) {
+        score += 3;
+    }
+    if has_link_or_mention(&name) {
+        score += 3;
+    }
+    if count_emoji(&raw_name) > NAME_EMOJI_THRESHOLD {
+        score += 3;
+    }
+    if DIGIT_USERNAME_RE.is_match(&username) {
+        score += 2;
+    }
+    if is_shouting(&raw_name) {
+        score += 1;
+    }
+    score
+}
+
+pub fn is_suspicious_user(user: &User, blacklist: &[String], threshold: i64) -> bool {
+    !user.is_bot && score_name(user, blacklist) >= threshold
+}
+
+// Member baru dinilai dari namanya saja, sebelum sempat mengirim pesan
+pub async fn handle_new_members(bot: Bot, db: Database, msg: Message) -> ResponseResult<()> {
+    let members = match msg.new_chat_members() {
+        Some(m) if !m.is_empty() => m,
+        _ => return Ok(()),
+    };
+
+    let (settings, blacklist, _) = match db.get_chat_data(msg.chat.id.0).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::warn!("Lewati member baru di {}, data grup tidak tersedia: {}", msg.chat.id, e);
+            return Ok(());
+        }
+    };
+    if !settings.is_enabled() {
+        return Ok(());
+    }
+
+    let mut removed_any = false;
+    for user in members {
+        let banned = db.is_banned(msg.chat.id.0, user.id.0 as i64).await.unwrap_or_else(|e| {
+            log::warn!("Cek ban gagal untuk {}: {}", user.id, e);
+            false
+        });
+        let reason = if banned {
+            "global/federation ban"
+        } else if settings.detectors.names && is_suspicious_user(user, &blacklist, settings.name_score_threshold) {
+            if settings.is_observe() {
+                message::report_action(&bot, &settings, Some(user), "names");
+                log::info!("[observe] member baru {} di {} ditandai oleh names", user.id, msg.chat.id);
+                continue;
+            }
+            "nama mencurigakan"
+        } else {
+            continue;
+        };
+
+        log::info!("Kick member baru {} di {}: {}", user.id, msg.chat.id, reason);
+        message::report_action(&bot, &settings, Some(user), reason);
+        let (chat_id, user_id) = (msg.chat.id, user.id);
+        // Ban dari global/federasi tetap permanen; nama mencurigakan hanya
+        // di-kick (ban lalu unban) supaya false positive bisa join lagi
+        let result = match queue::run(&bot, chat_id, "join_kick", move |bot| async move { bot.ban_chat_member(chat_id, user_id).await.map(drop) }).await {
+            Ok(()) if !banned => queue::run(&bot, chat_id, "join_kick", move |bot| async move { bot.unban_chat_member(chat_id, user_id).await.map(drop) }).await,
+            result => result,
+        };
+        // Satu member yang gagal tidak menghentikan member lain
+        if let Err(e) = result {
+            metrics::telegram_error(&e);
+            log::warn!("Gagal mengeluarkan member baru {} di {}: {}", user_id, chat_id, e);
+            continue;
+        }
+        metrics::ACTIONS.with_label_values(&["join_kick"]).inc();
+        removed_any = true;
+    }
+
+    if removed_any {
+        queue::delete(&bot, msg.chat.id, msg.id, "join");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teloxide::types::UserId;
+
+    fn user(first_name: &str, username: Option<&str>) -> User {
+        User {
+            id: UserId(1),
+            is_bot: false,
+            first_name: first_name.to_string(),
+            last_name: None,
+            username: username.map(str::to_string),
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        }
+    }
+
+    #[test]
+    fn shouting_keyword_name_is_flagged() {
+        let blacklist = vec!["vcs".to_string()];
+        let promo = user("PROMO VCS 24JAM", None);
+        assert_eq!(score_name(&promo, &blacklist), 4);
+        assert!(is_suspicious_user(&promo, &blacklist, 3));
+    }
+
+    #[test]
+    fn emoji_stuffed_name_is_flagged_on_its_own() {
+        let stuffed = user("Cari Cuan 🔥🔥🔥💰", None);
+        assert_eq!(score_name(&stuffed, &[]), 3);
+        assert!(is_suspicious_user(&stuffed, &[], 3));
+    }
+
+    #[test]
+    fn digit_username_counts_toward_threshold() {
+        let digits = user("Promo Murah", Some("promo83749201"));
+        assert_eq!(score_name(&digits, &[]), 2);
+        assert!(!is_suspicious_user(&digits, &[], 3));
+        assert!(is_suspicious_user(&digits, &[], 2));
+
+        let shouting = user("PROMO MURAH", Some("promo83749201"));
+        assert!(is_suspicious_user(&shouting, &[], 3));
+    }
+
+    #[test]
+    fn normal_names_are_not_flagged() {
+        assert_eq!(score_name(&user("Budi Santoso", Some("budi_s")), &[]), 0);
+        // Tahun lahir di username bukan angka acak
+        assert_eq!(score_name(&user("JOHNNY", Some("johnny1990")), &[]), 1);
+
+        let mut bot = user("PROMO 🔥🔥🔥🔥", Some("promo83749201_bot"));
+        bot.is_bot = true;
+        assert!(!is_suspicious_user(&bot, &[], 1));
+    }
+}
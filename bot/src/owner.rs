@@ -0,0 +1,215 @@
+use futures_util::stream::{self, StreamExt};
+use teloxide::{prelude::*, utils::command::BotCommands};
+use crate::{admins, queue};
+use crate::database::Database;
+use crate::error::{reply_on_db_error, HandlerResult};
+use crate::message::{self, normalize_keyword};
+use crate::config;
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase", description = "Command owner bot:")]
+pub enum OwnerCommand {
+    #[command(description = "Tambah keyword global.")]
+    Gaddbl(String),
+    #[command(description = "Hapus keyword global.")]
+    Gdelbl(String),
+    #[command(description = "Lihat semua keyword global.")]
+    Glistbl,
+    #[command(description = "Ban user di semua grup: /gban <user_id> [alasan].")]
+    Gban(String),
+    #[command(description = "Cabut global ban: /ungban <user_id>.")]
+    Ungban(String),
+    #[command(description = "Lihat semua grup yang dikenal bot.")]
+    Groups,
+    #[command(description = "Keluarkan bot dari grup: /leave <chat_id>.")]
+    Leave(String),
+    #[command(description = "Kirim pengumuman ke semua grup.")]
+    Broadcast(String),
+    #[command(description = "Muat ulang konfigurasi dan cache.")]
+    Reload,
+    #[command(description = "Lihat pemakaian memori cache.")]
+    Memstats,
+    #[command(description = "Tampilkan bantuan owner.")]
+    Ownerhelp,
+}
+
+// Jumlah pesan broadcast yang menunggu di antrian aksi sekaligus
+const BROADCAST_CONCURRENCY: usize = 16;
+
+// ID owner dari bot.owner_ids (atau env OWNER_IDS), bisa dimuat ulang lewat /reload
+pub fn is_owner(user_id: u64) -> bool {
+    config::get().bot.owner_ids.contains(&user_id)
+}
+
+// Target bisa dari argumen pertama atau dari pesan yang di-reply
+pub fn parse_target(msg: &Message, arg: &str) -> (Option<i64>, String) {
+    let mut parts = arg.trim().splitn(2, char::is_whitespace);
+    let first = parts.next().unwrap_or_default();
+
+    match first.parse::<i64>() {
+        Ok(id) => (Some(id), parts.next().unwrap_or_default().trim().to_string()),
+        Err(_) => {
+            let replied = msg.reply_to_message().and_then(|m| m.from()).map(|u| u.id.0 as i64);
+            (replied, arg.trim().to_string())
+        }
+    }
+}
+
+pub async fn handle_command(
+    bot: Bot,
+    db: Database,
+    msg: Message,
+    cmd: OwnerCommand,
+) -> ResponseResult<()> {
+    let result = run_command(&bot, &db, &msg, cmd).await;
+    reply_on_db_error(&bot, msg.chat.id, result).await
+}
+
+async fn run_command(bot: &Bot, db: &Database, msg: &Message, cmd: OwnerCommand) -> HandlerResult {
+    let owner_id = match msg.from() {
+        Some(u) if is_owner(u.id.0) => u.id.0 as i64,
+        _ => {
+            bot.send_message(msg.chat.id, "hanya owner bot yang dapat menggunakan perintah ini.").await?;
+            return Ok(());
+        }
+    };
+
+    match cmd {
+        OwnerCommand::Gaddbl(word) => {
+            let word = normalize_keyword(&word);
+            let text = if word.is_empty() {
+                "keyword tidak boleh kosong.".to_string()
+            } else if db.add_global_keyword(word.clone()).await? {
+                format!("ditambahkan ke blacklist global: `{}`", word)
+            } else {
+                format!("sudah ada di blacklist global: `{}`", word)
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        OwnerCommand::Gdelbl(word) => {
+            let word = normalize_keyword(&word);
+            let text = if db.remove_global_keyword(word.clone()).await? {
+                format!("dihapus dari blacklist global: `{}`", word)
+            } else {
+                format!("tidak ditemukan di blacklist global: `{}`", word)
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        OwnerCommand::Glistbl => {
+            let list = db.list_global_keywords().await?;
+            let text = if list.is_empty() {
+                "blacklist global kosong.".to_string()
+            } else {
+                format!("Blacklist global:\n{}", list.iter().map(|x| format!("- {}", x)).collect::<Vec<_>>().join("\n"))
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        OwnerCommand::Gban(arg) => {
+            let text = match parse_target(msg, &arg) {
+                (Some(user_id), reason) => {
+                    db.add_global_ban(user_id, reason, owner_id).await?;
+                    format!("user {} di-ban di semua grup.", user_id)
+                }
+                (None, _) => "format: /gban <user_id> [alasan], atau reply pesan user.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        OwnerCommand::Ungban(arg) => {
+            let text = match parse_target(msg, &arg) {
+                (Some(user_id), _) => {
+                    db.remove_global_ban(user_id).await?;
+                    format!("global ban untuk {} dicabut.", user_id)
+                }
+                (None, _) => "format: /ungban <user_id>, atau reply pesan user.".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        OwnerCommand::Groups => {
+            let groups = db.list_groups().await?;
+            let text = if groups.is_empty() {
+                "belum ada grup yang dikenal.".to_string()
+            } else {
+                format!(
+                    "Grup ({}):\n{}",
+                    groups.len(),
+                    groups.iter().map(|g| format!("- {} {}", g.group_id, g.title)).collect::<Vec<_>>().join("\n")
+                )
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        OwnerCommand::Leave(arg) => {
+            let text = match arg.trim().parse::<i64>() {
+                Ok(group_id) => match bot.leave_chat(ChatId(group_id)).await {
+                    Ok(_) => {
+                        db.forget_group(group_id).await?;
+                        format!("bot keluar dari {}.", group_id)
+                    }
+                    Err(e) => format!("gagal keluar dari {}: {}", group_id, e),
+                },
+                Err(_) => "format: /leave <chat_id>".to_string(),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        OwnerCommand::Broadcast(text) => {
+            if text.trim().is_empty() {
+                bot.send_message(msg.chat.id, "format: /broadcast <pesan>").await?;
+                return Ok(());
+            }
+
+            // Lewat antrian aksi supaya rate limit dan retry 429 tetap berlaku
+            let groups = db.list_groups().await?;
+            let chat_ids: Vec<ChatId> = groups.iter().map(|g| ChatId(g.group_id)).collect();
+            let sent = stream::iter(chat_ids)
+                .map(|chat_id| {
+                    let text = text.clone();
+                    queue::run(bot, chat_id, "broadcast", move |bot| {
+                        let text = text.clone();
+                        async move { bot.send_message(chat_id, text).await.map(drop) }
+                    })
+                })
+                .buffer_unordered(BROADCAST_CONCURRENCY)
+                .filter(|result| std::future::ready(result.is_ok()))
+                .count()
+                .await;
+            bot.send_message(msg.chat.id, format!("broadcast terkirim ke {}/{} grup.", sent, groups.len())).await?;
+        }
+        OwnerCommand::Reload => {
+            let text = match config::reload() {
+                Ok(ignored) => {
+                    // Cache hanya ditandai kedaluwarsa, bukan dikosongkan, supaya
+                    // data lama tetap bisa dipakai jika database sedang mati
+                    db.expire_caches();
+                    if ignored.is_empty() {
+                        "konfigurasi dimuat ulang, cache dimuat ulang dari database.".to_string()
+                    } else {
+                        format!(
+                            "konfigurasi dimuat ulang, cache dimuat ulang dari database. perubahan {} baru berlaku setelah restart.",
+                            ignored.join(", ")
+                        )
+                    }
+                }
+                Err(e) => format!("konfigurasi tidak valid, tetap memakai yang lama: {}", e),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        OwnerCommand::Memstats => {
+            let stats: Vec<_> = db.cache_stats()
+                .into_iter()
+                .chain(message::cache_stats())
+                .chain([admins::cache_stats()])
+                .collect();
+            let total: usize = stats.iter().map(|s| s.bytes).sum();
+            let lines = stats
+                .iter()
+                .map(|s| format!("- {}: {}/{} entry, ~{} KB", s.name, s.entries, s.capacity, s.bytes / 1024))
+                .collect::<Vec<_>>()
+                .join("\n");
+            bot.send_message(msg.chat.id, format!("Cache (~{} KB):\n{}", total / 1024, lines)).await?;
+        }
+        OwnerCommand::Ownerhelp => {
+            bot.send_message(msg.chat.id, OwnerCommand::descriptions().to_string()).await?;
+        }
+    }
+
+    Ok(())
+}
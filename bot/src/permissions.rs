@@ -0,0 +1,103 @@
+use teloxide::prelude::*;
+use teloxide::types::{ChatMember, ChatMemberKind};
+use crate::admins;
+use crate::database::Database;
+use crate::metrics;
+
+// Hak yang dibutuhkan command atau tombol panel, dipetakan ke hak admin Telegram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    // Admin mana pun atau moderator bot
+    View,
+    // can_delete_messages, atau moderator bot
+    DeleteMessages,
+    // can_restrict_members, atau moderator bot
+    RestrictMembers,
+    // can_change_info
+    ChangeInfo,
+    // can_promote_members, untuk mengelola moderator bot
+    PromoteMembers,
+    // Hanya pemilik grup
+    Creator,
+}
+
+impl Permission {
+    // Pemilik grup selalu lolos; hak admin lain dicek sesuai kebutuhan
+    fn granted_to(self, member: &ChatMember) -> bool {
+        match self {
+            Permission::View => member.is_privileged(),
+            Permission::DeleteMessages => member.can_delete_messages(),
+            Permission::RestrictMembers => member.can_restrict_members(),
+            // Helper teloxide untuk hak ini juga memperhitungkan member restricted
+            Permission::ChangeInfo => match &member.kind {
+                ChatMemberKind::Owner(_) => true,
+                ChatMemberKind::Administrator(admin) => admin.can_change_info,
+                _ => false,
+            },
+            Permission::PromoteMembers => member.can_promote_members(),
+            Permission::Creator => member.is_owner(),
+        }
+    }
+
+    // Moderator bot bukan admin Telegram, jadi hanya diberi hak moderasi
+    fn granted_to_moderator(self) -> bool {
+        matches!(self, Permission::View | Permission::DeleteMessages | Permission::RestrictMembers)
+    }
+
+    pub fn denied_text(self) -> &'static str {
+        match self {
+            Permission::View => "hanya admin atau moderator bot yang dapat menggunakan perintah ini.",
+            Permission::DeleteMessages => "butuh hak admin \"hapus pesan\" atau status moderator bot.",
+            Permission::RestrictMembers => "butuh hak admin \"blokir pengguna\" atau status moderator bot.",
+            Permission::ChangeInfo => "butuh hak admin \"ubah info grup\".",
+            Permission::PromoteMembers => "butuh hak admin \"tambah admin baru\".",
+            Permission::Creator => "hanya pemilik grup yang dapat menggunakan perintah ini.",
+        }
+    }
+}
+
+async fn is_moderator(db: &Database, chat_id: i64, user_id: i64) -> bool {
+    match db.get_settings(chat_id).await {
+        Ok(settings) => settings.moderators.contains(&user_id),
+        Err(e) => {
+            log::warn!("Gagal memuat moderator {}: {}", chat_id, e);
+            false
+        }
+    }
+}
+
+// Admin Telegram sesuai haknya, atau moderator bot untuk hak moderasi
+pub async fn user_has(bot: &Bot, db: &Database, chat_id: i64, user_id: i64, permission: Permission) -> bool {
+    if admins::member(bot, chat_id, user_id).await.is_some_and(|member| permission.granted_to(&member)) {
+        return true;
+    }
+    permission.granted_to_moderator() && is_moderator(db, chat_id, user_id).await
+}
+
+// Admin anonim tidak bisa dibedakan satu sama lain, jadi hanya diberi hak
+// yang dimiliki semua admin anonim di grup
+async fn anonymous_has(bot: &Bot, chat_id: i64, permission: Permission) -> bool {
+    match admins::get(bot, chat_id).await {
+        Ok(list) => {
+            let mut anonymous = list.iter().filter(|member| member.is_anonymous()).peekable();
+            anonymous.peek().is_some() && anonymous.all(|member| permission.granted_to(member))
+        }
+        Err(e) => {
+            metrics::telegram_error(&e);
+            log::debug!("Gagal memuat admin {}: {}", chat_id, e);
+            false
+        }
+    }
+}
+
+// Hak pengirim pesan terhadap grup target. Admin anonim hanya dikenali di
+// dalam grupnya sendiri.
+pub async fn sender_has(bot: &Bot, db: &Database, msg: &Message, chat_id: i64, permission: Permission) -> bool {
+    if chat_id == msg.chat.id.0 && admins::is_anonymous_admin(msg) {
+        return anonymous_has(bot, chat_id, permission).await;
+    }
+    match msg.from() {
+        Some(user) => user_has(bot, db, chat_id, user.id.0 as i64, permission).await,
+        None => false,
+    }
+}
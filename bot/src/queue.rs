@@ -0,0 +1,362 @@
+use dashmap::DashMap;
+use futures_util::future::BoxFuture;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+use teloxide::RequestError;
+use tokio::sync::{mpsc, oneshot};
+use crate::bot_api;
+use crate::config;
+use crate::metrics;
+
+// Antrian aksi ke Telegram API. Setiap chat punya worker sendiri supaya aksi
+// di satu chat berurutan dan dibatasi per chat, sementara batas global
+// dibagi semua worker. Error 429 dan error jaringan dicoba ulang.
+
+// deleteMessages menerima maksimal 100 id per request
+const MAX_BATCH: usize = 100;
+// Worker chat berhenti setelah sekian lama tanpa aksi
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+type Call = Box<dyn FnMut() -> BoxFuture<'static, ResponseResult<()>> + Send>;
+
+enum Job {
+    Delete { bot: Bot, message_id: MessageId, detector: &'static str },
+    Call { name: &'static str, call: Call, done: Option<oneshot::Sender<ResponseResult<()>>> },
+}
+
+static QUEUES: Lazy<DashMap<i64, mpsc::UnboundedSender<Job>>> = Lazy::new(DashMap::new);
+static GLOBAL: Lazy<Mutex<Bucket>> = Lazy::new(|| Mutex::new(Bucket::new(config::get().ratelimit.global_per_sec)));
+
+// Token bucket sederhana; kapasitas sama dengan rate (burst satu detik)
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl Bucket {
+    fn new(rate: u32) -> Self {
+        Self { tokens: rate as f64, updated: Instant::now(), blocked_until: None }
+    }
+
+    // Waktu tunggu sampai satu request boleh dikirim, nol jika bisa sekarang
+    fn wait(&mut self, rate: u32, now: Instant) -> Duration {
+        let rate = rate as f64;
+        self.tokens = (self.tokens + now.duration_since(self.updated).as_secs_f64() * rate).min(rate);
+        self.updated = now;
+        if let Some(until) = self.blocked_until.filter(|until| *until > now) {
+            return until - now;
+        }
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / rate)
+        }
+    }
+
+    fn block(&mut self, duration: Duration) {
+        self.blocked_until = Some(Instant::now() + duration);
+    }
+}
+
+// Tunggu sampai chat boleh mengirim, tanpa mengambil token
+async fn chat_ready(bucket: &mut Bucket) {
+    loop {
+        let wait = bucket.wait(config::get().ratelimit.chat_per_sec, Instant::now());
+        if wait.is_zero() {
+            return;
+        }
+        tokio::time::sleep(wait).await;
+    }
+}
+
+// Ambil token dari bucket chat dan bucket global
+async fn acquire(bucket: &mut Bucket) {
+    loop {
+        chat_ready(bucket).await;
+        let wait = {
+            let mut global = GLOBAL.lock();
+            let wait = global.wait(config::get().ratelimit.global_per_sec, Instant::now());
+            if wait.is_zero() {
+                global.tokens -= 1.0;
+                bucket.tokens -= 1.0;
+                return;
+            }
+            wait
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+// Kirim satu request dengan rate limit, ulangi untuk 429 dan error jaringan.
+// Error terakhir dikembalikan tanpa dicatat ke metrik; itu tugas pemanggil.
+async fn execute<F>(bucket: &mut Bucket, mut send: F) -> ResponseResult<()>
+where
+    F: FnMut() -> BoxFuture<'static, ResponseResult<()>>,
+{
+    let mut attempt = 0;
+    loop {
+        acquire(bucket).await;
+        let error = match send().await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        let reason = match &error {
+            RequestError::RetryAfter(after) => {
+                bucket.block(*after);
+                "retry_after"
+            }
+            RequestError::Network(_) | RequestError::Io(_) => {
+                bucket.block((Duration::from_secs(1) * 2u32.pow(attempt.min(5))).min(MAX_BACKOFF));
+                "network"
+            }
+            _ => return Err(error),
+        };
+        if attempt >= config::get().ratelimit.max_retries {
+            return Err(error);
+        }
+        attempt += 1;
+        metrics::telegram_error(&error);
+        metrics::ACTION_RETRIES.with_label_values(&[reason]).inc();
+        log::debug!("Request gagal ({}), percobaan ulang ke-{}", error, attempt);
+    }
+}
+
+async fn delete_one(bot: &Bot, bucket: &mut Bucket, chat_id: ChatId, message_id: MessageId) -> ResponseResult<()> {
+    execute(bucket, || {
+        let bot = bot.clone();
+        Box::pin(async move { bot.delete_message(chat_id, message_id).await.map(drop) })
+    })
+    .await
+}
+
+async fn delete_batch(bot: &Bot, bucket: &mut Bucket, chat_id: ChatId, mut batch: Vec<(MessageId, &'static str)>) {
+    // Pesan yang sama bisa ditandai lebih dari sekali
+    batch.sort_by_key(|(id, _)| id.0);
+    batch.dedup_by_key(|(id, _)| id.0);
+
+    if let [(message_id, detector)] = batch[..] {
+        match delete_one(bot, bucket, chat_id, message_id).await {
+            Ok(()) => metrics::MESSAGES_DELETED.with_label_values(&[detector]).inc(),
+            Err(e) => {
+                metrics::telegram_error(&e);
+                log::debug!("Gagal menghapus pesan {} di {}: {}", message_id, chat_id, e);
+            }
+        }
+        return;
+    }
+
+    let ids: Vec<i32> = batch.iter().map(|(id, _)| id.0).collect();
+    match execute(bucket, || {
+        let (bot, ids) = (bot.clone(), ids.clone());
+        Box::pin(async move { bot_api::delete_messages(&bot, chat_id, &ids).await })
+    })
+    .await
+    {
+        Ok(()) => {
+            for (_, detector) in &batch {
+                metrics::MESSAGES_DELETED.with_label_values(&[detector]).inc();
+            }
+        }
+        // Bot API server lama tanpa deleteMessages: hapus satu per satu
+        Err(RequestError::Api(e)) => {
+            log::debug!("deleteMessages gagal di {} ({}), hapus satu per satu", chat_id, e);
+            for (message_id, detector) in batch {
+                match delete_one(bot, bucket, chat_id, message_id).await {
+                    Ok(()) => metrics::MESSAGES_DELETED.with_label_values(&[detector]).inc(),
+                    Err(e) => {
+                        metrics::telegram_error(&e);
+                        log::debug!("Gagal menghapus pesan {} di {}: {}", message_id, chat_id, e);
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            metrics::telegram_error(&e);
+            log::debug!("Gagal menghapus {} pesan di {}: {}", batch.len(), chat_id, e);
+        }
+    }
+}
+
+async fn worker(chat_id: ChatId, mut rx: mpsc::UnboundedReceiver<Job>) {
+    let mut bucket = Bucket::new(config::get().ratelimit.chat_per_sec);
+    let mut deferred: VecDeque<Job> = VecDeque::new();
+
+    loop {
+        let job = match deferred.pop_front() {
+            Some(job) => job,
+            None => match tokio::time::timeout(IDLE_TIMEOUT, rx.recv()).await {
+                Ok(Some(job)) => job,
+                Ok(None) => return,
+                Err(_) => {
+                    // Lepas dari daftar dulu supaya aksi baru membuat worker
+                    // baru, lalu selesaikan aksi yang sempat masuk
+                    QUEUES.remove(&chat_id.0);
+                    rx.close();
+                    continue;
+                }
+            },
+        };
+
+        match job {
+            Job::Delete { bot, message_id, detector } => {
+                // Tunggu giliran dulu; hapus lain yang masuk selama menunggu
+                // ikut digabung dalam satu deleteMessages
+                chat_ready(&mut bucket).await;
+                let mut batch = vec![(message_id, detector)];
+                while batch.len() < MAX_BATCH {
+                    match rx.try_recv() {
+                        Ok(Job::Delete { message_id, detector, .. }) => batch.push((message_id, detector)),
+                        Ok(other) => deferred.push_back(other),
+                        Err(_) => break,
+                    }
+                }
+                let count = batch.len();
+                delete_batch(&bot, &mut bucket, chat_id, batch).await;
+                metrics::ACTION_QUEUE_PENDING.sub(count as i64);
+            }
+            Job::Call { name, call, done } => {
+                let result = execute(&mut bucket, call).await;
+                metrics::ACTION_QUEUE_PENDING.dec();
+                match done {
+                    Some(done) => {
+                        let _ = done.send(result);
+                    }
+                    None => {
+                        if let Err(e) = result {
+                            metrics::telegram_error(&e);
+                            log::debug!("Aksi {} di {} gagal: {}", name, chat_id, e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn enqueue(chat_id: ChatId, job: Job) {
+    metrics::ACTION_QUEUE_PENDING.inc();
+    let spawn_worker = || {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(worker(chat_id, rx));
+        tx
+    };
+
+    let sent = QUEUES.entry(chat_id.0).or_insert_with(spawn_worker).send(job);
+    if let Err(mpsc::error::SendError(job)) = sent {
+        // Worker berhenti tidak normal (panic); buat ulang
+        QUEUES.remove(&chat_id.0);
+        let _ = QUEUES.entry(chat_id.0).or_insert_with(spawn_worker).send(job);
+    }
+}
+
+fn boxed_call<F, Fut>(bot: &Bot, mut call: F) -> Call
+where
+    F: FnMut(Bot) -> Fut + Send + 'static,
+    Fut: Future<Output = ResponseResult<()>> + Send + 'static,
+{
+    let bot = bot.clone();
+    Box::new(move || Box::pin(call(bot.clone())))
+}
+
+// Hapus pesan lewat antrian tanpa menunggu hasil. `detector` dipakai untuk
+// metrik pesan terhapus.
+pub fn delete(bot: &Bot, chat_id: ChatId, message_id: MessageId, detector: &'static str) {
+    enqueue(chat_id, Job::Delete { bot: bot.clone(), message_id, detector });
+}
+
+// Jalankan aksi lewat antrian tanpa menunggu hasil; kegagalan hanya dicatat
+pub fn spawn<F, Fut>(bot: &Bot, chat_id: ChatId, name: &'static str, call: F)
+where
+    F: FnMut(Bot) -> Fut + Send + 'static,
+    Fut: Future<Output = ResponseResult<()>> + Send + 'static,
+{
+    enqueue(chat_id, Job::Call { name, call: boxed_call(bot, call), done: None });
+}
+
+// Jalankan aksi lewat antrian dan tunggu hasilnya
+pub async fn run<F, Fut>(bot: &Bot, chat_id: ChatId, name: &'static str, call: F) -> ResponseResult<()>
+where
+    F: FnMut(Bot) -> Fut + Send + 'static,
+    Fut: Future<Output = ResponseResult<()>> + Send + 'static,
+{
+    let (done, result) = oneshot::channel();
+    enqueue(chat_id, Job::Call { name, call: boxed_call(bot, call), done: Some(done) });
+    result
+        .await
+        .unwrap_or_else(|_| Err(RequestError::Io(std::io::Error::other("antrian aksi berhenti"))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use teloxide::ApiError;
+
+    #[test]
+    fn bucket_refills_at_rate() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(2);
+        assert_eq!(bucket.wait(2, start), Duration::ZERO);
+
+        bucket.tokens = 0.0;
+        assert_eq!(bucket.wait(2, start), Duration::from_millis(500));
+        // Setengah detik kemudian satu token sudah terisi lagi
+        assert_eq!(bucket.wait(2, start + Duration::from_millis(500)), Duration::ZERO);
+        // Tidak melebihi kapasitas
+        bucket.wait(2, start + Duration::from_secs(10));
+        assert_eq!(bucket.tokens, 2.0);
+    }
+
+    #[test]
+    fn bucket_waits_while_blocked() {
+        let mut bucket = Bucket::new(10);
+        bucket.block(Duration::from_secs(5));
+        let wait = bucket.wait(10, Instant::now());
+        assert!(wait > Duration::from_secs(4) && wait <= Duration::from_secs(5), "wait = {:?}", wait);
+    }
+
+    // Gagal `failures` kali dengan `error`, lalu berhasil
+    fn flaky(failures: u32, error: fn() -> RequestError) -> (Arc<AtomicU32>, impl FnMut() -> BoxFuture<'static, ResponseResult<()>>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let send = move || {
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            let result = if call < failures { Err(error()) } else { Ok(()) };
+            Box::pin(async move { result }) as BoxFuture<'static, ResponseResult<()>>
+        };
+        (calls, send)
+    }
+
+    #[tokio::test]
+    async fn execute_retries_after_rate_limit() {
+        let mut bucket = Bucket::new(100);
+        let (calls, send) = flaky(2, || RequestError::RetryAfter(Duration::from_millis(10)));
+        assert!(execute(&mut bucket, send).await.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn execute_returns_api_errors_without_retry() {
+        let mut bucket = Bucket::new(100);
+        let (calls, send) = flaky(1, || RequestError::Api(ApiError::MessageToDeleteNotFound));
+        assert!(matches!(execute(&mut bucket, send).await, Err(RequestError::Api(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn execute_gives_up_after_max_retries() {
+        let mut bucket = Bucket::new(100);
+        let (calls, send) = flaky(u32::MAX, || RequestError::RetryAfter(Duration::from_millis(1)));
+        assert!(matches!(execute(&mut bucket, send).await, Err(RequestError::RetryAfter(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), config::get().ratelimit.max_retries + 1);
+    }
+}
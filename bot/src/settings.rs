@@ -0,0 +1,263 @@
+use teloxide::prelude::*;
+use teloxide::{ApiError, RequestError};
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use mongodb::bson::Bson;
+use std::ops::RangeInclusive;
+use crate::permissions::{self, Permission};
+use crate::database::Database;
+use crate::error::{DbResult, HandlerError, HandlerResult, DB_ERROR_REPLY};
+use crate::models::{
+    ActionPolicy, DetectorToggles, GroupMode, GroupSettings, EMOJI_THRESHOLDS, LANGUAGES, NAME_SCORE_THRESHOLDS,
+    SPAM_THRESHOLDS, STRIKE_LIMITS,
+};
+
+// Format callback data: "set:<group_id>:<key>:<value>"
+const PREFIX: &str = "set";
+
+fn button(label: String, group_id: i64, key: &str, value: &str) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(label, format!("{}:{}:{}:{}", PREFIX, group_id, key, value))
+}
+
+fn mark(label: &str, active: bool) -> String {
+    if active {
+        format!("✅ {}", label)
+    } else {
+        label.to_string()
+    }
+}
+
+// Baris [-] [nilai] [+] untuk threshold numerik
+fn stepper(label: String, group_id: i64, key: &str) -> Vec<InlineKeyboardButton> {
+    vec![
+        button("➖".to_string(), group_id, key, "-"),
+        button(label, group_id, "noop", ""),
+        button("➕".to_string(), group_id, key, "+"),
+    ]
+}
+
+fn panel_text(settings: &GroupSettings) -> String {
+    format!(
+        "Pengaturan anti-gcast untuk grup {}\nMode: {}\nHukuman: {} setelah {} strike\nLog channel: {}",
+        settings.group_id,
+        settings.mode.as_str(),
+        settings.action.as_str(),
+        settings.strike_limit,
+        settings.log_channel.map_or_else(|| "-".to_string(), |c| c.to_string()),
+    )
+}
+
+fn panel_keyboard(settings: &GroupSettings) -> InlineKeyboardMarkup {
+    let group_id = settings.group_id;
+    let mut rows = Vec::new();
+
+    rows.push(
+        GroupMode::ALL
+            .into_iter()
+            .map(|m| button(mark(m.as_str(), settings.mode == m), group_id, "mode", m.as_str()))
+            .collect(),
+    );
+
+    rows.push(
+        ActionPolicy::ALL
+            .into_iter()
+            .map(|p| button(mark(p.as_str(), settings.action == p), group_id, "action", p.as_str()))
+            .collect(),
+    );
+
+    // Toggle detector, dua per baris
+    let detectors: Vec<InlineKeyboardButton> = DetectorToggles::NAMES
+        .into_iter()
+        .map(|name| {
+            let enabled = settings.detectors.get(name).unwrap_or(false);
+            let label = if enabled { format!("🟢 {}", name) } else { format!("🔴 {}", name) };
+            button(label, group_id, "det", name)
+        })
+        .collect();
+    rows.extend(detectors.chunks(2).map(|chunk| chunk.to_vec()));
+
+    rows.push(stepper(format!("Strike: {}", settings.strike_limit), group_id, "strikes"));
+    rows.push(stepper(format!("Emoji: {}", settings.emoji_threshold), group_id, "emoji"));
+    rows.push(stepper(format!("Skor nama: {}", settings.name_score_threshold), group_id, "namescore"));
+    rows.push(stepper(format!("Spam: {:.2}", settings.spam_threshold), group_id, "spam"));
+
+    rows.push(
+        LANGUAGES
+            .into_iter()
+            .map(|lang| button(mark(lang, settings.language == lang), group_id, "lang", lang))
+            .collect(),
+    );
+
+    rows.push(vec![button("Tutup".to_string(), group_id, "close", "")]);
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+pub async fn send_panel(bot: &Bot, db: &Database, chat_id: ChatId, group_id: i64) -> HandlerResult {
+    let settings = db.get_settings(group_id).await?;
+    bot.send_message(chat_id, panel_text(&settings))
+        .reply_markup(panel_keyboard(&settings))
+        .await?;
+    Ok(())
+}
+
+fn step_int(current: i64, direction: &str, bounds: &RangeInclusive<i64>) -> i64 {
+    let next = if direction == "+" { current + 1 } else { current - 1 };
+    next.clamp(*bounds.start(), *bounds.end())
+}
+
+// Hak yang dibutuhkan tiap tombol, sama dengan command padanannya. Perubahan
+// yang melemahkan proteksi (mode off/observe, mematikan detector, menaikkan
+// threshold) setara dengan /off dan hanya untuk pemilik grup.
+fn required_permission(settings: &GroupSettings, key: &str, value: &str) -> Permission {
+    match key {
+        "mode" if GroupMode::parse(value) != Some(GroupMode::On) => Permission::Creator,
+        "det" if settings.detectors.get(value).unwrap_or(false) => Permission::Creator,
+        "emoji" | "namescore" | "spam" if value == "+" => Permission::Creator,
+        "lang" => Permission::ChangeInfo,
+        "action" | "strikes" => Permission::RestrictMembers,
+        "mode" | "det" | "emoji" | "namescore" | "spam" => Permission::DeleteMessages,
+        _ => Permission::View,
+    }
+}
+
+// Terapkan satu perubahan dari tombol. Mengembalikan false jika data tidak dikenal.
+async fn apply_change(db: &Database, settings: &GroupSettings, key: &str, value: &str) -> DbResult<bool> {
+    let group_id = settings.group_id;
+
+    match key {
+        "mode" => match GroupMode::parse(value) {
+            Some(mode) => db.set_mode(group_id, mode).await?,
+            None => return Ok(false),
+        },
+        "lang" if LANGUAGES.contains(&value) => db.set_language(group_id, value).await?,
+        "action" => match ActionPolicy::parse(value) {
+            Some(policy) => db.set_action(group_id, policy).await?,
+            None => return Ok(false),
+        },
+        "det" => match settings.detectors.get(value) {
+            Some(enabled) => db.set_detector(group_id, value, !enabled).await?,
+            None => return Ok(false),
+        },
+        "strikes" => {
+            db.set_strike_limit(group_id, step_int(settings.strike_limit, value, &STRIKE_LIMITS)).await?;
+        }
+        "emoji" => {
+            let next = step_int(settings.emoji_threshold, value, &EMOJI_THRESHOLDS);
+            db.set_threshold(group_id, "emoji_threshold", Bson::Int64(next)).await?;
+        }
+        "namescore" => {
+            let next = step_int(settings.name_score_threshold, value, &NAME_SCORE_THRESHOLDS);
+            db.set_threshold(group_id, "name_score_threshold", Bson::Int64(next)).await?;
+        }
+        "spam" => {
+            let delta = if value == "+" { 0.05 } else { -0.05 };
+            // Bulatkan ke 2 desimal supaya tidak menumpuk error floating point
+            let next = ((settings.spam_threshold + delta) * 100.0).round() / 100.0;
+            db.set_threshold(group_id, "spam_threshold", Bson::Double(next.clamp(*SPAM_THRESHOLDS.start(), *SPAM_THRESHOLDS.end()))).await?;
+        }
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+pub async fn handle_callback(bot: Bot, db: Database, q: CallbackQuery) -> ResponseResult<()> {
+    let query_id = q.id.clone();
+    match run_callback(&bot, &db, q).await {
+        Ok(()) => Ok(()),
+        Err(HandlerError::Db(e)) => {
+            // Error database ditampilkan sebagai notifikasi callback
+            log::error!("Database error di panel settings: {}", e);
+            bot.answer_callback_query(query_id).text(DB_ERROR_REPLY).await?;
+            Ok(())
+        }
+        Err(HandlerError::Request(e)) => Err(e),
+    }
+}
+
+async fn run_callback(bot: &Bot, db: &Database, q: CallbackQuery) -> HandlerResult {
+    let data = q.data.clone().unwrap_or_default();
+    let mut parts = data.splitn(4, ':');
+
+    let (Some(PREFIX), Some(group_id), Some(key), value) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    };
+    let Ok(group_id) = group_id.parse::<i64>() else {
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    };
+    let value = value.unwrap_or_default();
+
+    let settings = db.get_settings(group_id).await?;
+    let permission = required_permission(&settings, key, value);
+    if !permissions::user_has(bot, db, group_id, q.from.id.0 as i64, permission).await {
+        bot.answer_callback_query(q.id).text(permission.denied_text()).await?;
+        return Ok(());
+    }
+
+    let Some(message) = q.message.as_ref() else {
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    };
+
+    // Callback dijawab sebelum panel diubah, supaya tombol tidak terus loading
+    // jika edit atau hapus pesan gagal
+    match key {
+        "noop" => {
+            bot.answer_callback_query(q.id).await?;
+        }
+        "close" => {
+            bot.answer_callback_query(q.id).await?;
+            bot.delete_message(message.chat.id, message.id).await?;
+        }
+        _ => {
+            let updated = match apply_change(db, &settings, key, value).await? {
+                true => Some(db.get_settings(group_id).await?),
+                false => None,
+            };
+            bot.answer_callback_query(q.id).await?;
+
+            if let Some(settings) = updated {
+                let edited = bot
+                    .edit_message_text(message.chat.id, message.id, panel_text(&settings))
+                    .reply_markup(panel_keyboard(&settings))
+                    .await;
+                match edited {
+                    // Nilai yang sudah di batas stepper tidak mengubah isi panel
+                    Err(RequestError::Api(ApiError::MessageNotModified)) => {}
+                    result => {
+                        result?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weakening_protection_needs_creator() {
+        let settings = GroupSettings::new(1);
+        assert_eq!(required_permission(&settings, "mode", "off"), Permission::Creator);
+        assert_eq!(required_permission(&settings, "mode", "observe"), Permission::Creator);
+        assert_eq!(required_permission(&settings, "mode", "on"), Permission::DeleteMessages);
+        assert_eq!(required_permission(&settings, "spam", "+"), Permission::Creator);
+        assert_eq!(required_permission(&settings, "namescore", "-"), Permission::DeleteMessages);
+        assert_eq!(required_permission(&settings, "strikes", "+"), Permission::RestrictMembers);
+    }
+
+    #[test]
+    fn disabling_detector_needs_creator() {
+        let mut settings = GroupSettings::new(1);
+        settings.detectors.links = true;
+        settings.detectors.emoji = false;
+        assert_eq!(required_permission(&settings, "det", "links"), Permission::Creator);
+        assert_eq!(required_permission(&settings, "det", "emoji"), Permission::DeleteMessages);
+    }
+}
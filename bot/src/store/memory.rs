@@ -0,0 +1,310 @@
+use async_trait::async_trait;
+use mongodb::bson::{DateTime, Document};
+use parking_lot::Mutex;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use crate::classifier::{self, BayesModel};
+use crate::error::DbResult;
+use crate::models::{ConfigTemplate, Federation, GroupSettings, KnownGroup, SpamExample};
+use super::{apply_settings_fields, ListKind, Store};
+
+#[derive(Default)]
+struct MemoryData {
+    settings: HashMap<i64, GroupSettings>,
+    strikes: HashMap<(i64, i64), i64>,
+    lists: HashMap<(ListKind, i64), BTreeSet<String>>,
+    global_keywords: BTreeSet<String>,
+    classifiers: HashMap<i64, BayesModel>,
+    token_updated: HashMap<(i64, String), DateTime>,
+    examples: Vec<SpamExample>,
+    groups: HashMap<i64, KnownGroup>,
+    global_bans: HashSet<i64>,
+    federations: HashMap<String, Federation>,
+    fed_bans: HashMap<String, HashSet<i64>>,
+    fed_invites: HashMap<String, (String, DateTime)>,
+    connections: HashMap<i64, i64>,
+    templates: HashMap<(i64, String), ConfigTemplate>,
+    markers: HashSet<String>,
+}
+
+// Backend tanpa persistensi: data hilang saat bot restart.
+// Cocok untuk uji coba lokal dan test yang tidak punya database.
+#[derive(Default)]
+pub struct MemoryStore {
+    data: Mutex<MemoryData>,
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn get_settings(&self, group_id: i64) -> DbResult<Option<GroupSettings>> {
+        Ok(self.data.lock().settings.get(&group_id).cloned())
+    }
+
+    async fn update_settings(&self, group_id: i64, fields: Document) -> DbResult<()> {
+        let mut data = self.data.lock();
+        let current = data.settings.get(&group_id).cloned().unwrap_or_else(|| GroupSettings::new(group_id));
+        data.settings.insert(group_id, apply_settings_fields(current, &fields)?);
+        Ok(())
+    }
+
+    async fn add_strike(&self, group_id: i64, user_id: i64) -> DbResult<i64> {
+        let mut data = self.data.lock();
+        let count = data.strikes.entry((group_id, user_id)).or_default();
+        *count += 1;
+        Ok(*count)
+    }
+
+    async fn reset_strikes(&self, group_id: i64, user_id: i64) -> DbResult<()> {
+        self.data.lock().strikes.remove(&(group_id, user_id));
+        Ok(())
+    }
+
+    async fn add_keyword(&self, kind: ListKind, group_id: i64, keyword: &str) -> DbResult<bool> {
+        Ok(self.data.lock().lists.entry((kind, group_id)).or_default().insert(keyword.to_string()))
+    }
+
+    async fn add_keywords(&self, kind: ListKind, group_id: i64, keywords: Vec<String>) -> DbResult<()> {
+        self.data.lock().lists.entry((kind, group_id)).or_default().extend(keywords);
+        Ok(())
+    }
+
+    async fn remove_keyword(&self, kind: ListKind, group_id: i64, keyword: &str) -> DbResult<bool> {
+        let mut data = self.data.lock();
+        Ok(data.lists.get_mut(&(kind, group_id)).is_some_and(|list| list.remove(keyword)))
+    }
+
+    async fn clear_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<u64> {
+        let removed = self.data.lock().lists.remove(&(kind, group_id));
+        Ok(removed.map_or(0, |list| list.len() as u64))
+    }
+
+    async fn list_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<Vec<String>> {
+        let data = self.data.lock();
+        Ok(data.lists.get(&(kind, group_id)).map(|list| list.iter().cloned().collect()).unwrap_or_default())
+    }
+
+    async fn add_global_keyword(&self, keyword: &str) -> DbResult<bool> {
+        Ok(self.data.lock().global_keywords.insert(keyword.to_string()))
+    }
+
+    async fn remove_global_keyword(&self, keyword: &str) -> DbResult<bool> {
+        Ok(self.data.lock().global_keywords.remove(keyword))
+    }
+
+    async fn list_global_keywords(&self) -> DbResult<Vec<String>> {
+        Ok(self.data.lock().global_keywords.iter().cloned().collect())
+    }
+
+    async fn train_classifier(&self, scope: i64, tokens: &[String], is_spam: bool) -> DbResult<()> {
+        let mut data = self.data.lock();
+        let data = &mut *data;
+        let model = data.classifiers.entry(scope).or_default();
+
+        if is_spam {
+            model.spam_docs += 1;
+        } else {
+            model.ham_docs += 1;
+        }
+        for token in tokens {
+            let (spam, ham) = model.tokens.entry(token.clone()).or_default();
+            if is_spam {
+                *spam += 1;
+            } else {
+                *ham += 1;
+            }
+            data.token_updated.insert((scope, token.clone()), DateTime::now());
+        }
+        Ok(())
+    }
+
+    async fn load_classifier(&self, scope: i64) -> DbResult<BayesModel> {
+        let data = self.data.lock();
+        let Some(model) = data.classifiers.get(&scope) else {
+            return Ok(BayesModel::default());
+        };
+        Ok(BayesModel {
+            spam_docs: model.spam_docs,
+            ham_docs: model.ham_docs,
+            tokens: classifier::most_frequent(&model.tokens, classifier::MAX_VOCABULARY),
+        })
+    }
+
+    async fn prune_classifier(&self, cutoff: DateTime) -> DbResult<u64> {
+        let mut data = self.data.lock();
+        let data = &mut *data;
+        let mut removed = 0;
+        for (scope, model) in data.classifiers.iter_mut() {
+            model.tokens.retain(|token, (spam, ham)| {
+                let stale = data.token_updated.get(&(*scope, token.clone())).is_none_or(|at| *at < cutoff);
+                let keep = *spam + *ham > 1 || !stale;
+                if !keep {
+                    data.token_updated.remove(&(*scope, token.clone()));
+                    removed += 1;
+                }
+                keep
+            });
+        }
+        Ok(removed)
+    }
+
+    async fn record_example(&self, example: SpamExample) -> DbResult<bool> {
+        let mut data = self.data.lock();
+        let duplicate = example.message_id.is_some()
+            && data.examples.iter().any(|e| e.group_id == example.group_id && e.message_id == example.message_id);
+        if duplicate {
+            return Ok(false);
+        }
+        data.examples.push(example);
+        Ok(true)
+    }
+
+    async fn list_ham_texts(&self, group_id: i64) -> DbResult<Vec<String>> {
+        let data = self.data.lock();
+        Ok(data.examples
+            .iter()
+            .filter(|e| e.group_id == group_id && !e.is_spam)
+            .map(|e| e.text.clone())
+            .collect())
+    }
+
+    async fn track_group(&self, group_id: i64, title: &str) -> DbResult<()> {
+        self.data.lock().groups.insert(group_id, KnownGroup {
+            id: None,
+            group_id,
+            title: title.to_string(),
+            updated_at: DateTime::now(),
+        });
+        Ok(())
+    }
+
+    async fn forget_group(&self, group_id: i64) -> DbResult<()> {
+        self.data.lock().groups.remove(&group_id);
+        Ok(())
+    }
+
+    async fn list_groups(&self) -> DbResult<Vec<KnownGroup>> {
+        Ok(self.data.lock().groups.values().cloned().collect())
+    }
+
+    async fn add_global_ban(&self, user_id: i64, _reason: &str, _banned_by: i64) -> DbResult<()> {
+        self.data.lock().global_bans.insert(user_id);
+        Ok(())
+    }
+
+    async fn remove_global_ban(&self, user_id: i64) -> DbResult<()> {
+        self.data.lock().global_bans.remove(&user_id);
+        Ok(())
+    }
+
+    async fn list_global_bans(&self) -> DbResult<HashSet<i64>> {
+        Ok(self.data.lock().global_bans.clone())
+    }
+
+    async fn create_federation(&self, federation: &Federation) -> DbResult<()> {
+        self.data.lock().federations.insert(federation.fed_id.clone(), federation.clone());
+        Ok(())
+    }
+
+    async fn get_federation(&self, fed_id: &str) -> DbResult<Option<Federation>> {
+        Ok(self.data.lock().federations.get(fed_id).cloned())
+    }
+
+    async fn federation_of(&self, group_id: i64) -> DbResult<Option<Federation>> {
+        let data = self.data.lock();
+        Ok(data.federations.values().find(|f| f.groups.contains(&group_id)).cloned())
+    }
+
+    async fn add_federation_group(&self, fed_id: &str, group_id: i64) -> DbResult<()> {
+        if let Some(federation) = self.data.lock().federations.get_mut(fed_id) {
+            if !federation.groups.contains(&group_id) {
+                federation.groups.push(group_id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_federation_group(&self, group_id: i64) -> DbResult<()> {
+        for federation in self.data.lock().federations.values_mut() {
+            federation.groups.retain(|&g| g != group_id);
+        }
+        Ok(())
+    }
+
+    async fn set_fed_share_blacklist(&self, fed_id: &str, share: bool, source: i64) -> DbResult<()> {
+        if let Some(federation) = self.data.lock().federations.get_mut(fed_id) {
+            federation.share_blacklist = share;
+            federation.blacklist_source = Some(source);
+        }
+        Ok(())
+    }
+
+    async fn create_fed_invite(&self, token: &str, fed_id: &str, expires_at: DateTime) -> DbResult<()> {
+        let mut data = self.data.lock();
+        let now = DateTime::now();
+        data.fed_invites.retain(|_, (_, expires)| *expires > now);
+        data.fed_invites.insert(token.to_string(), (fed_id.to_string(), expires_at));
+        Ok(())
+    }
+
+    async fn take_fed_invite(&self, token: &str) -> DbResult<Option<String>> {
+        let invite = self.data.lock().fed_invites.remove(token);
+        Ok(invite.filter(|(_, expires)| *expires > DateTime::now()).map(|(fed_id, _)| fed_id))
+    }
+
+    async fn add_fed_ban(&self, fed_id: &str, user_id: i64, _reason: &str, _banned_by: i64) -> DbResult<()> {
+        self.data.lock().fed_bans.entry(fed_id.to_string()).or_default().insert(user_id);
+        Ok(())
+    }
+
+    async fn remove_fed_ban(&self, fed_id: &str, user_id: i64) -> DbResult<()> {
+        if let Some(users) = self.data.lock().fed_bans.get_mut(fed_id) {
+            users.remove(&user_id);
+        }
+        Ok(())
+    }
+
+    async fn list_fed_bans(&self, fed_id: &str) -> DbResult<HashSet<i64>> {
+        Ok(self.data.lock().fed_bans.get(fed_id).cloned().unwrap_or_default())
+    }
+
+    async fn set_connection(&self, user_id: i64, group_id: i64) -> DbResult<()> {
+        self.data.lock().connections.insert(user_id, group_id);
+        Ok(())
+    }
+
+    async fn clear_connection(&self, user_id: i64) -> DbResult<()> {
+        self.data.lock().connections.remove(&user_id);
+        Ok(())
+    }
+
+    async fn get_connection(&self, user_id: i64) -> DbResult<Option<i64>> {
+        Ok(self.data.lock().connections.get(&user_id).copied())
+    }
+
+    async fn save_template(&self, template: ConfigTemplate) -> DbResult<()> {
+        self.data.lock().templates.insert((template.owner_id, template.name.clone()), template);
+        Ok(())
+    }
+
+    async fn get_template(&self, owner_id: i64, name: &str) -> DbResult<Option<ConfigTemplate>> {
+        Ok(self.data.lock().templates.get(&(owner_id, name.to_string())).cloned())
+    }
+
+    async fn delete_template(&self, owner_id: i64, name: &str) -> DbResult<()> {
+        self.data.lock().templates.remove(&(owner_id, name.to_string()));
+        Ok(())
+    }
+
+    async fn list_templates(&self, owner_id: i64) -> DbResult<Vec<ConfigTemplate>> {
+        let data = self.data.lock();
+        Ok(data.templates.values().filter(|t| t.owner_id == owner_id).cloned().collect())
+    }
+
+    async fn has_marker(&self, name: &str) -> DbResult<bool> {
+        Ok(self.data.lock().markers.contains(name))
+    }
+
+    async fn set_marker(&self, name: &str) -> DbResult<()> {
+        self.data.lock().markers.insert(name.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+use futures_util::future::BoxFuture;
+use futures_util::stream::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::{FindOptions, IndexOptions};
+use mongodb::{Collection, IndexModel};
+use std::collections::HashSet;
+use std::time::Duration;
+use crate::error::DbResult;
+use crate::message::normalize_keyword;
+use crate::models::upgrade_settings;
+use super::mongo::MongoStore;
+use super::MigrationReport;
+
+// Migrasi skema MongoDB. Setiap migrasi dijalankan sekali dan dicatat di
+// koleksi `migrations`. Urutan tidak boleh diubah; migrasi baru selalu
+// ditambahkan di akhir dan harus aman dijalankan ulang jika gagal di tengah.
+struct Migration {
+    name: &'static str,
+    run: for<'a> fn(&'a MongoStore) -> BoxFuture<'a, DbResult<()>>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration { name: "0001_dedupe_keywords", run: dedupe_keywords },
+    Migration { name: "0002_create_indexes", run: create_indexes },
+    Migration { name: "0003_settings_v1", run: upgrade_settings_documents },
+    Migration { name: "0004_unique_example_message", run: unique_example_message },
+    Migration { name: "0005_fed_invite_expiry", run: fed_invite_expiry },
+    Migration { name: "0006_template_owner_key", run: template_owner_key },
+];
+
+fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    matches!(e.kind.as_ref(), ErrorKind::Write(WriteFailure::WriteError(w)) if w.code == 11000)
+}
+
+// Klaim migrasi berlaku selama LEASE dan diperpanjang selama migrasi masih
+// berjalan. Klaim yang kedaluwarsa berarti instance pemiliknya mati di tengah
+// migrasi, jadi boleh diambil alih.
+const LEASE: Duration = Duration::from_secs(10 * 60);
+const LEASE_RENEW_INTERVAL: Duration = Duration::from_secs(60);
+
+fn lease_expiry() -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() + LEASE.as_millis() as i64)
+}
+
+// Klaim lewat _id unik supaya beberapa instance tidak menjalankan migrasi
+// yang sama bersamaan. Mengembalikan false jika klaim milik instance lain
+// masih berlaku.
+async fn claim(store: &MongoStore, name: &str) -> DbResult<bool> {
+    let now = DateTime::now();
+    let claim = doc! { "_id": name, "state": "running", "started_at": now, "expires_at": lease_expiry() };
+    match store.migrations.insert_one(claim, None).await {
+        Ok(_) => return Ok(true),
+        Err(e) if is_duplicate_key(&e) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    // Klaim lama tanpa expires_at berasal dari versi sebelum lease dan dianggap kedaluwarsa
+    let stale = doc! {
+        "_id": name,
+        "state": "running",
+        "$or": [{ "expires_at": { "$lt": now } }, { "expires_at": { "$exists": false } }],
+    };
+    let takeover = doc! { "$set": { "started_at": now, "expires_at": lease_expiry() } };
+    let taken = store.migrations.update_one(stale, takeover, None).await?.modified_count == 1;
+    if taken {
+        log::warn!("Klaim migrasi {} kedaluwarsa, diambil alih", name);
+    }
+    Ok(taken)
+}
+
+// Jalankan satu migrasi sambil memperpanjang klaimnya
+async fn run_claimed(store: &MongoStore, migration: &Migration) -> DbResult<()> {
+    let run = (migration.run)(store);
+    tokio::pin!(run);
+    let mut renew = tokio::time::interval_at(tokio::time::Instant::now() + LEASE_RENEW_INTERVAL, LEASE_RENEW_INTERVAL);
+    loop {
+        tokio::select! {
+            result = &mut run => return result,
+            _ = renew.tick() => {
+                let extend = doc! { "$set": { "expires_at": lease_expiry() } };
+                if let Err(e) = store.migrations.update_one(doc! { "_id": migration.name }, extend, None).await {
+                    log::warn!("Gagal memperpanjang klaim migrasi {}: {}", migration.name, e);
+                }
+            }
+        }
+    }
+}
+
+// Jalankan migrasi yang belum tercatat, berurutan. Migrasi berikutnya bisa
+// bergantung pada yang sebelumnya, jadi begitu satu migrasi masih diklaim
+// instance lain, sisanya dilaporkan sebagai pending.
+pub async fn run(store: &MongoStore) -> DbResult<MigrationReport> {
+    let done: HashSet<String> = store
+        .migrations
+        .find(doc! { "state": "done" }, None)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .iter()
+        .filter_map(|d| d.get_str("_id").ok().map(str::to_string))
+        .collect();
+
+    let mut report = MigrationReport::default();
+    let mut remaining = MIGRATIONS.iter().filter(|m| !done.contains(m.name));
+    while let Some(migration) = remaining.next() {
+        if !claim(store, migration.name).await? {
+            report.pending.push(migration.name);
+            report.pending.extend(remaining.map(|m| m.name));
+            break;
+        }
+
+        log::info!("Menjalankan migrasi {}", migration.name);
+        if let Err(e) = run_claimed(store, migration).await {
+            // Lepas klaim supaya dicoba lagi pada start berikutnya
+            if let Err(release) = store.migrations.delete_one(doc! { "_id": migration.name }, None).await {
+                log::warn!("Gagal melepas klaim migrasi {}: {}", migration.name, release);
+            }
+            return Err(e);
+        }
+
+        store
+            .migrations
+            .update_one(
+                doc! { "_id": migration.name },
+                doc! { "$set": { "state": "done", "applied_at": DateTime::now() }, "$unset": { "expires_at": "" } },
+                None,
+            )
+            .await?;
+        report.applied.push(migration.name);
+    }
+
+    Ok(report)
+}
+
+// Keyword dari sebelum normalisasi bisa berbeda huruf/spasi dan ganda.
+// Normalisasi semua keyword dan sisakan dokumen tertua per keyword.
+fn dedupe_keywords(store: &MongoStore) -> BoxFuture<'_, DbResult<()>> {
+    Box::pin(async move {
+        let collections = [
+            ("blacklist", store.blacklist.clone_with_type::<Document>()),
+            ("whitelist", store.whitelist.clone_with_type()),
+            ("global_keywords", store.global_keywords.clone_with_type()),
+        ];
+        for (name, collection) in collections {
+            let (removed, renamed) = dedupe_collection(&collection).await?;
+            log::info!("{}: {} duplikat dihapus, {} keyword dinormalisasi", name, removed, renamed);
+        }
+        Ok(())
+    })
+}
+
+async fn dedupe_collection(collection: &Collection<Document>) -> DbResult<(u64, usize)> {
+    let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+    let docs: Vec<Document> = collection.find(None, options).await?.try_collect().await?;
+
+    // global_keywords tidak punya group_id
+    let mut seen: HashSet<(Option<i64>, String)> = HashSet::new();
+    let mut duplicates: Vec<ObjectId> = Vec::new();
+    let mut renames: Vec<(ObjectId, String)> = Vec::new();
+    for document in &docs {
+        let (Ok(id), Ok(keyword)) = (document.get_object_id("_id"), document.get_str("keyword")) else {
+            continue;
+        };
+        let normalized = normalize_keyword(keyword);
+        if normalized.is_empty() || !seen.insert((document.get_i64("group_id").ok(), normalized.clone())) {
+            duplicates.push(id);
+        } else if normalized != keyword {
+            renames.push((id, normalized));
+        }
+    }
+
+    // Hapus dulu supaya rename tidak bentrok dengan unique index yang mungkin sudah ada
+    let removed = if duplicates.is_empty() {
+        0
+    } else {
+        collection.delete_many(doc! { "_id": { "$in": duplicates } }, None).await?.deleted_count
+    };
+    for (id, keyword) in &renames {
+        collection.update_one(doc! { "_id": id }, doc! { "$set": { "keyword": keyword } }, None).await?;
+    }
+
+    Ok((removed, renames.len()))
+}
+
+// Unique index untuk keyword (butuh 0001) dan index pencarian untuk filter
+// yang dipakai MongoStore. createIndex idempoten untuk definisi yang sama.
+fn create_indexes(store: &MongoStore) -> BoxFuture<'_, DbResult<()>> {
+    Box::pin(async move {
+        let index = |keys: Document, unique: bool| {
+            IndexModel::builder()
+                .keys(keys)
+                .options(IndexOptions::builder().unique(unique).build())
+                .build()
+        };
+
+        store.blacklist.create_index(index(doc! { "group_id": 1, "keyword": 1 }, true), None).await?;
+        store.whitelist.create_index(index(doc! { "group_id": 1, "keyword": 1 }, true), None).await?;
+        store.global_keywords.create_index(index(doc! { "keyword": 1 }, true), None).await?;
+
+        store.settings.create_index(index(doc! { "group_id": 1 }, false), None).await?;
+        store.strikes.create_index(index(doc! { "group_id": 1, "user_id": 1 }, false), None).await?;
+        store.bayes_tokens.create_index(index(doc! { "scope": 1, "token": 1 }, false), None).await?;
+        store.bayes_totals.create_index(index(doc! { "scope": 1 }, false), None).await?;
+        store.examples.create_index(index(doc! { "group_id": 1, "is_spam": 1 }, false), None).await?;
+        store.groups.create_index(index(doc! { "group_id": 1 }, false), None).await?;
+        store.global_bans.create_index(index(doc! { "user_id": 1 }, false), None).await?;
+        store.federations.create_index(index(doc! { "fed_id": 1 }, false), None).await?;
+        store.federations.create_index(index(doc! { "groups": 1 }, false), None).await?;
+        store.fed_bans.create_index(index(doc! { "fed_id": 1, "user_id": 1 }, false), None).await?;
+        store.connections.create_index(index(doc! { "user_id": 1 }, false), None).await?;
+        store.templates.create_index(index(doc! { "name": 1 }, false), None).await?;
+        Ok(())
+    })
+}
+
+// Upgrade semua dokumen settings sekaligus, tidak menunggu dibaca satu per satu
+fn upgrade_settings_documents(store: &MongoStore) -> BoxFuture<'_, DbResult<()>> {
+    Box::pin(async move {
+        let raw = store.settings.clone_with_type::<Document>();
+        let mut cursor = raw.find(None, None).await?;
+        let mut upgraded = 0;
+        while let Some(mut document) = cursor.try_next().await? {
+            let Ok(id) = document.get_object_id("_id") else {
+                continue;
+            };
+            if upgrade_settings(&mut document) {
+                raw.replace_one(doc! { "_id": id }, &document, None).await?;
+                upgraded += 1;
+            }
+        }
+        log::info!("settings: {} dokumen di-upgrade", upgraded);
+        Ok(())
+    })
+}
+
+// Satu pesan hanya dilatih sekali. Contoh lama tanpa message_id tidak ikut
+// index supaya tidak dianggap duplikat satu sama lain.
+fn unique_example_message(store: &MongoStore) -> BoxFuture<'_, DbResult<()>> {
+    Box::pin(async move {
+        let options = IndexOptions::builder()
+            .unique(true)
+            .partial_filter_expression(doc! { "message_id": { "$exists": true } })
+            .build();
+        let index = IndexModel::builder().keys(doc! { "group_id": 1, "message_id": 1 }).options(options).build();
+        store.examples.create_index(index, None).await?;
+        Ok(())
+    })
+}
+
+// Undangan federasi yang kedaluwarsa dihapus otomatis oleh TTL index
+fn fed_invite_expiry(store: &MongoStore) -> BoxFuture<'_, DbResult<()>> {
+    Box::pin(async move {
+        let options = IndexOptions::builder().expire_after(Duration::ZERO).build();
+        let index = IndexModel::builder().keys(doc! { "expires_at": 1 }).options(options).build();
+        store.fed_invites.create_index(index, None).await?;
+        Ok(())
+    })
+}
+
+// Nama template unik per pembuat, bukan global. Index lama dari 0002 dilepas.
+fn template_owner_key(store: &MongoStore) -> BoxFuture<'_, DbResult<()>> {
+    Box::pin(async move {
+        let options = IndexOptions::builder().unique(true).build();
+        let index = IndexModel::builder().keys(doc! { "owner_id": 1, "name": 1 }).options(options).build();
+        store.templates.create_index(index, None).await?;
+        if let Err(e) = store.templates.drop_index("name_1", None).await {
+            log::debug!("Index templates name_1 tidak dihapus: {}", e);
+        }
+        Ok(())
+    })
+}
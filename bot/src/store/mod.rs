@@ -0,0 +1,238 @@
+use async_trait::async_trait;
+use mongodb::bson::{self, Bson, DateTime, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::mpsc::UnboundedSender;
+use crate::classifier::BayesModel;
+use crate::error::{DbError, DbResult};
+use crate::models::{ConfigTemplate, Federation, GroupSettings, KnownGroup, SpamExample};
+
+pub mod memory;
+mod migrations;
+pub mod mongo;
+pub mod sqlite;
+
+pub use memory::MemoryStore;
+pub use mongo::MongoStore;
+pub use sqlite::SqliteStore;
+
+// Daftar keyword per grup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListKind {
+    Blacklist,
+    Whitelist,
+}
+
+impl ListKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ListKind::Blacklist => "blacklist",
+            ListKind::Whitelist => "whitelist",
+        }
+    }
+}
+
+// Hasil satu kali menjalankan migrasi. `pending` berisi migrasi yang belum
+// diterapkan karena masih diklaim instance lain, berikut semua migrasi
+// sesudahnya.
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub applied: Vec<&'static str>,
+    pub pending: Vec<&'static str>,
+}
+
+// Cara instance lain diberi tahu perubahan data: change stream MongoDB
+// (butuh replica set) atau polling koleksi `revisions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncMode {
+    Off,
+    ChangeStream,
+    Poll,
+}
+
+// Cache yang perlu dibuang setelah data berubah. None berarti seluruh isi
+// cache tersebut, dipakai saat grup yang berubah tidak diketahui.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invalidation {
+    Settings(Option<i64>),
+    List(ListKind, Option<i64>),
+    GlobalKeywords,
+    GlobalBans,
+    Federations,
+    FedBans(Option<String>),
+    Classifier(Option<i64>),
+    Ham(Option<i64>),
+}
+
+impl Invalidation {
+    // Koleksi sumber data cache ini
+    pub fn collection(&self) -> &'static str {
+        match self {
+            Invalidation::Settings(_) => "settings",
+            Invalidation::List(kind, _) => kind.as_str(),
+            Invalidation::GlobalKeywords => "global_keywords",
+            Invalidation::GlobalBans => "global_bans",
+            Invalidation::Federations => "federations",
+            Invalidation::FedBans(_) => "fed_bans",
+            Invalidation::Classifier(_) => "bayes_totals",
+            Invalidation::Ham(_) => "examples",
+        }
+    }
+
+    // Kebalikan dari collection(); dokumen (jika ada) dipakai untuk mempersempit ke satu grup
+    pub fn from_collection(collection: &str, document: Option<&Document>) -> Option<Self> {
+        let field = |name: &str| document.and_then(|d| d.get_i64(name).ok());
+        match collection {
+            "settings" => Some(Invalidation::Settings(field("group_id"))),
+            "blacklist" => Some(Invalidation::List(ListKind::Blacklist, field("group_id"))),
+            "whitelist" => Some(Invalidation::List(ListKind::Whitelist, field("group_id"))),
+            "global_keywords" => Some(Invalidation::GlobalKeywords),
+            "global_bans" => Some(Invalidation::GlobalBans),
+            "federations" => Some(Invalidation::Federations),
+            "fed_bans" => Some(Invalidation::FedBans(
+                document.and_then(|d| d.get_str("fed_id").ok()).map(str::to_string),
+            )),
+            "bayes_totals" => Some(Invalidation::Classifier(field("scope"))),
+            "examples" => Some(Invalidation::Ham(field("group_id"))),
+            _ => None,
+        }
+    }
+}
+
+// Backend penyimpanan mentah. Cache dan normalisasi keyword diurus Database,
+// jadi implementasi di sini cukup membaca dan menulis apa adanya.
+#[async_trait]
+pub trait Store: Send + Sync {
+    // Settings. `fields` berisi pasangan field -> nilai ala `$set`, termasuk
+    // path bertitik seperti "detectors.links".
+    async fn get_settings(&self, group_id: i64) -> DbResult<Option<GroupSettings>>;
+    async fn update_settings(&self, group_id: i64, fields: Document) -> DbResult<()>;
+
+    // Strike per user per grup
+    async fn add_strike(&self, group_id: i64, user_id: i64) -> DbResult<i64>;
+    async fn reset_strikes(&self, group_id: i64, user_id: i64) -> DbResult<()>;
+
+    // Blacklist/whitelist grup. Keyword yang diterima sudah dinormalisasi.
+    async fn add_keyword(&self, kind: ListKind, group_id: i64, keyword: &str) -> DbResult<bool>;
+    async fn add_keywords(&self, kind: ListKind, group_id: i64, keywords: Vec<String>) -> DbResult<()>;
+    async fn remove_keyword(&self, kind: ListKind, group_id: i64, keyword: &str) -> DbResult<bool>;
+    async fn clear_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<u64>;
+    async fn list_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<Vec<String>>;
+
+    async fn add_global_keyword(&self, keyword: &str) -> DbResult<bool>;
+    async fn remove_global_keyword(&self, keyword: &str) -> DbResult<bool>;
+    async fn list_global_keywords(&self) -> DbResult<Vec<String>>;
+
+    // Classifier naive Bayes dan contoh feedback admin (audit)
+    async fn train_classifier(&self, scope: i64, tokens: &[String], is_spam: bool) -> DbResult<()>;
+    // Maksimal classifier::MAX_VOCABULARY token, yang paling sering muncul
+    async fn load_classifier(&self, scope: i64) -> DbResult<BayesModel>;
+    // Hapus token dengan total kemunculan 1 yang terakhir dilatih sebelum `cutoff`
+    async fn prune_classifier(&self, cutoff: DateTime) -> DbResult<u64>;
+    // Mengembalikan false jika pesan yang sama (group_id, message_id) sudah tercatat
+    async fn record_example(&self, example: SpamExample) -> DbResult<bool>;
+    async fn list_ham_texts(&self, group_id: i64) -> DbResult<Vec<String>>;
+
+    async fn track_group(&self, group_id: i64, title: &str) -> DbResult<()>;
+    async fn forget_group(&self, group_id: i64) -> DbResult<()>;
+    async fn list_groups(&self) -> DbResult<Vec<KnownGroup>>;
+
+    async fn add_global_ban(&self, user_id: i64, reason: &str, banned_by: i64) -> DbResult<()>;
+    async fn remove_global_ban(&self, user_id: i64) -> DbResult<()>;
+    async fn list_global_bans(&self) -> DbResult<HashSet<i64>>;
+
+    // Federasi. Satu grup maksimal di satu federasi; Database yang menjaga ini.
+    async fn create_federation(&self, federation: &Federation) -> DbResult<()>;
+    async fn get_federation(&self, fed_id: &str) -> DbResult<Option<Federation>>;
+    async fn federation_of(&self, group_id: i64) -> DbResult<Option<Federation>>;
+    async fn add_federation_group(&self, fed_id: &str, group_id: i64) -> DbResult<()>;
+    async fn remove_federation_group(&self, group_id: i64) -> DbResult<()>;
+    async fn set_fed_share_blacklist(&self, fed_id: &str, share: bool, source: i64) -> DbResult<()>;
+    // Undangan sekali pakai. take_fed_invite menghapus token dan mengembalikan
+    // fed_id-nya jika token ada dan belum kedaluwarsa.
+    async fn create_fed_invite(&self, token: &str, fed_id: &str, expires_at: DateTime) -> DbResult<()>;
+    async fn take_fed_invite(&self, token: &str) -> DbResult<Option<String>>;
+    async fn add_fed_ban(&self, fed_id: &str, user_id: i64, reason: &str, banned_by: i64) -> DbResult<()>;
+    async fn remove_fed_ban(&self, fed_id: &str, user_id: i64) -> DbResult<()>;
+    async fn list_fed_bans(&self, fed_id: &str) -> DbResult<HashSet<i64>>;
+
+    async fn set_connection(&self, user_id: i64, group_id: i64) -> DbResult<()>;
+    async fn clear_connection(&self, user_id: i64) -> DbResult<()>;
+    async fn get_connection(&self, user_id: i64) -> DbResult<Option<i64>>;
+
+    // Template per pembuat: nama hanya unik untuk satu owner_id
+    async fn save_template(&self, template: ConfigTemplate) -> DbResult<()>;
+    async fn get_template(&self, owner_id: i64, name: &str) -> DbResult<Option<ConfigTemplate>>;
+    async fn delete_template(&self, owner_id: i64, name: &str) -> DbResult<()>;
+    async fn list_templates(&self, owner_id: i64) -> DbResult<Vec<ConfigTemplate>>;
+
+    // Penanda langkah satu kali, mis. pengisian keyword global bawaan
+    async fn has_marker(&self, name: &str) -> DbResult<bool>;
+    async fn set_marker(&self, name: &str) -> DbResult<()>;
+
+    // Cek koneksi ke backend untuk /readyz. Backend lokal selalu siap.
+    async fn ping(&self) -> DbResult<()> {
+        Ok(())
+    }
+
+    // Terapkan migrasi skema yang belum dijalankan dan kembalikan namanya.
+    // SQLite membuat skema lengkap saat dibuka dan meng-upgrade settings saat
+    // dibaca, jadi default-nya tidak melakukan apa pun.
+    async fn migrate(&self) -> DbResult<MigrationReport> {
+        Ok(MigrationReport::default())
+    }
+
+    // Sinkronisasi cache antar instance. Backend satu proses tidak perlu
+    // melakukan apa pun, jadi default-nya kosong.
+    async fn publish_change(&self, _change: &Invalidation) -> DbResult<()> {
+        Ok(())
+    }
+
+    // Kirim perubahan dari instance lain ke `tx` sampai channel ditutup
+    async fn watch_changes(&self, _mode: SyncMode, _tx: UnboundedSender<Invalidation>) -> DbResult<()> {
+        Ok(())
+    }
+}
+
+// Terapkan update ala `$set` ke settings, untuk backend yang menyimpan
+// settings sebagai satu dokumen utuh (SQLite, memory). Path bertitik yang
+// induknya tidak ada ditolak, bukan dibuang diam-diam.
+fn apply_settings_fields(settings: GroupSettings, fields: &Document) -> DbResult<GroupSettings> {
+    let mut doc = bson::to_document(&settings)?;
+    for (key, value) in fields {
+        match key.split_once('.') {
+            Some((parent, child)) => match doc.get_mut(parent) {
+                Some(Bson::Document(inner)) => {
+                    inner.insert(child, value.clone());
+                }
+                _ => return Err(DbError::Backend(format!("field settings {} tidak punya induk {}", key, parent))),
+            },
+            None => {
+                doc.insert(key, value.clone());
+            }
+        }
+    }
+    Ok(bson::from_document(doc)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn apply_settings_fields_sets_top_level_and_nested() {
+        let fields = doc! { "strike_limit": 7_i64, "detectors.links": false };
+        let settings = apply_settings_fields(GroupSettings::new(1), &fields).unwrap();
+        assert_eq!(settings.strike_limit, 7);
+        assert!(!settings.detectors.links);
+        assert!(settings.detectors.keywords);
+    }
+
+    #[test]
+    fn apply_settings_fields_rejects_missing_parent() {
+        let fields = doc! { "unknown.child": true };
+        assert!(apply_settings_fields(GroupSettings::new(1), &fields).is_err());
+    }
+}
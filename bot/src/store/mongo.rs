@@ -0,0 +1,631 @@
+use async_trait::async_trait;
+use mongodb::{Client, Collection, Cursor, bson::{self, doc, DateTime, Document}};
+use mongodb::options::{
+    ClientOptions, FindOneAndUpdateOptions, FindOptions, InsertManyOptions, ReplaceOptions,
+    ReturnDocument, UpdateOptions,
+};
+use mongodb::change_stream::event::ChangeStreamEvent;
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::{ChangeStreamOptions, FullDocumentType};
+use futures_util::future::try_join_all;
+use futures_util::stream::TryStreamExt;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use crate::classifier::{self, BayesModel};
+use crate::config::StorageConfig;
+use crate::metrics::MongoMetrics;
+use crate::error::{DbError, DbResult};
+use crate::models::{
+    BlacklistItem, ClassifierTotals, ConfigTemplate, Connection, FedBan, Federation, GlobalBan, GlobalKeyword,
+    GroupSettings, KnownGroup, SpamExample, StrikeRecord, TokenStat, WhitelistItem, upgrade_settings,
+};
+use super::{migrations, Invalidation, ListKind, MigrationReport, Store, SyncMode};
+
+// Insert keyword hanya jika belum ada. Mengembalikan true jika dokumen baru dibuat.
+async fn upsert_keyword<T>(collection: &Collection<T>, filter: Document) -> DbResult<bool> {
+    let result = collection
+        .update_one(filter.clone(), doc! { "$setOnInsert": filter }, UpdateOptions::builder().upsert(true).build())
+        .await?;
+    Ok(result.upserted_id.is_some())
+}
+
+fn unordered_insert() -> InsertManyOptions {
+    InsertManyOptions::builder().ordered(false).build()
+}
+
+// Insert unordered tetap mengembalikan error jika sebagian dokumen ditolak unique index.
+// Duplikat memang disengaja diabaikan, error lain diteruskan.
+fn ignore_duplicates(result: mongodb::error::Result<mongodb::results::InsertManyResult>) -> DbResult<()> {
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => match e.kind.as_ref() {
+            ErrorKind::BulkWrite(failure)
+                if failure.write_concern_error.is_none()
+                    && failure.write_errors.iter().flatten().all(|w| w.code == 11000) => Ok(()),
+            _ => Err(e.into()),
+        },
+    }
+}
+
+// Kode error server jika change stream dipakai tanpa replica set
+const CHANGE_STREAM_UNSUPPORTED: i32 = 40573;
+
+// Interval polling koleksi `revisions`
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+async fn collect<T: DeserializeOwned + Unpin + Send + Sync>(cursor: Cursor<T>) -> DbResult<Vec<T>> {
+    Ok(cursor.try_collect().await?)
+}
+
+#[derive(Clone)]
+pub struct MongoStore {
+    pub blacklist: Collection<BlacklistItem>,
+    pub whitelist: Collection<WhitelistItem>,
+    pub settings: Collection<GroupSettings>,
+    pub global_keywords: Collection<GlobalKeyword>,
+    pub bayes_tokens: Collection<TokenStat>,
+    pub bayes_totals: Collection<ClassifierTotals>,
+    pub strikes: Collection<StrikeRecord>,
+    pub examples: Collection<SpamExample>,
+    pub groups: Collection<KnownGroup>,
+    pub global_bans: Collection<GlobalBan>,
+    pub federations: Collection<Federation>,
+    pub fed_bans: Collection<FedBan>,
+    pub fed_invites: Collection<Document>,
+    pub connections: Collection<Connection>,
+    pub templates: Collection<ConfigTemplate>,
+    // Penghitung perubahan per koleksi, untuk sinkronisasi cache mode poll
+    pub revisions: Collection<Document>,
+    // Migrasi skema yang sudah diterapkan, lihat store::migrations
+    pub migrations: Collection<Document>,
+    // Penanda langkah satu kali di luar migrasi
+    pub markers: Collection<Document>,
+    db: mongodb::Database,
+}
+
+impl MongoStore {
+    pub async fn connect(config: &StorageConfig) -> DbResult<Self> {
+        let mut client_options = ClientOptions::parse(&config.mongodb_uri).await?;
+        // Optimasi koneksi MongoDB
+        client_options.max_pool_size = Some(config.max_pool_size);
+        client_options.min_pool_size = Some(config.min_pool_size);
+        client_options.max_idle_time = Some(Duration::from_secs(config.max_idle_secs));
+        client_options.server_selection_timeout = Some(Duration::from_secs(config.server_selection_timeout_secs));
+        client_options.command_event_handler = Some(Arc::new(MongoMetrics));
+
+        let client = Client::with_options(client_options)?;
+        let db = client.database(&config.database);
+
+        let store = Self {
+            blacklist: db.collection("blacklist"),
+            whitelist: db.collection("whitelist"),
+            settings: db.collection("settings"),
+            global_keywords: db.collection("global_keywords"),
+            bayes_tokens: db.collection("bayes_tokens"),
+            bayes_totals: db.collection("bayes_totals"),
+            strikes: db.collection("strikes"),
+            examples: db.collection("examples"),
+            groups: db.collection("groups"),
+            global_bans: db.collection("global_bans"),
+            federations: db.collection("federations"),
+            fed_bans: db.collection("fed_bans"),
+            fed_invites: db.collection("fed_invites"),
+            connections: db.collection("connections"),
+            templates: db.collection("templates"),
+            revisions: db.collection("revisions"),
+            migrations: db.collection("migrations"),
+            markers: db.collection("markers"),
+            db,
+        };
+
+        Ok(store)
+    }
+
+    // Kedua daftar punya bentuk dokumen yang sama
+    fn list_collection(&self, kind: ListKind) -> Collection<Document> {
+        match kind {
+            ListKind::Blacklist => self.blacklist.clone_with_type(),
+            ListKind::Whitelist => self.whitelist.clone_with_type(),
+        }
+    }
+
+    // Change stream seluruh database; berhenti dengan Err jika server tidak mendukung
+    async fn watch_change_stream(&self, tx: &UnboundedSender<Invalidation>) -> DbResult<()> {
+        // UpdateLookup supaya event update membawa group_id dokumennya
+        let options = ChangeStreamOptions::builder()
+            .full_document(Some(FullDocumentType::UpdateLookup))
+            .build();
+        let mut stream = self.db.watch(None, options).await?;
+
+        while let Some(event) = stream.try_next().await? {
+            let ChangeStreamEvent { ns, full_document, .. } = event;
+            let Some(collection) = ns.and_then(|ns| ns.coll) else {
+                continue;
+            };
+            if let Some(change) = Invalidation::from_collection(&collection, full_document.as_ref()) {
+                if tx.send(change).is_err() {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Fallback tanpa replica set: bandingkan nomor revisi tiap koleksi setiap detik.
+    // Hanya menangkap perubahan dari bot (lewat publish_change), bukan edit langsung ke Mongo.
+    async fn poll_revisions(&self, tx: &UnboundedSender<Invalidation>) -> DbResult<()> {
+        let mut seen: HashMap<String, i64> = HashMap::new();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let docs = match self.revisions.find(None, None).await {
+                Ok(cursor) => collect(cursor).await,
+                Err(e) => Err(e.into()),
+            };
+            let docs = match docs {
+                Ok(docs) => docs,
+                Err(e) => {
+                    // Gangguan sementara tidak menghentikan polling
+                    log::warn!("Gagal membaca revisions: {}", e);
+                    continue;
+                }
+            };
+
+            for doc in docs {
+                let (Ok(collection), Ok(rev)) = (doc.get_str("_id"), doc.get_i64("rev")) else {
+                    continue;
+                };
+                // Revisi pertama yang terlihat hanya dijadikan patokan
+                let previous = seen.insert(collection.to_string(), rev);
+                if previous.is_some_and(|p| p != rev) {
+                    if let Some(change) = Invalidation::from_collection(collection, None) {
+                        if tx.send(change).is_err() {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn fetch_user_ids<T>(collection: &Collection<T>, filter: Document) -> DbResult<HashSet<i64>> {
+        let find_options = FindOptions::builder()
+            .projection(doc! { "user_id": 1, "_id": 0 })
+            .build();
+
+        let cursor = collection
+            .clone_with_type::<Document>()
+            .find(filter, find_options)
+            .await?;
+
+        let docs = collect(cursor).await?;
+        Ok(docs.iter().filter_map(|d| d.get_i64("user_id").ok()).collect())
+    }
+}
+
+#[async_trait]
+impl Store for MongoStore {
+    async fn get_settings(&self, group_id: i64) -> DbResult<Option<GroupSettings>> {
+        let raw = self.settings.clone_with_type::<Document>();
+        let Some(mut document) = raw.find_one(doc! { "group_id": group_id }, None).await? else {
+            return Ok(None);
+        };
+
+        // Dokumen skema lama di-upgrade saat dibaca lalu ditulis ulang
+        if upgrade_settings(&mut document) {
+            if let Ok(id) = document.get_object_id("_id") {
+                raw.replace_one(doc! { "_id": id }, &document, None).await?;
+            }
+        }
+        Ok(Some(bson::from_document(document)?))
+    }
+
+    async fn update_settings(&self, group_id: i64, fields: Document) -> DbResult<()> {
+        // Dokumen baru langsung ditulis lengkap dengan default skema terbaru,
+        // sama seperti backend lain, supaya tidak bergantung pada default serde.
+        // Field yang di-$set (termasuk induk path bertitik) tidak boleh ikut.
+        let mut on_insert = bson::to_document(&GroupSettings::new(group_id))?;
+        on_insert.remove("group_id");
+        for key in fields.keys() {
+            on_insert.remove(key.split('.').next().unwrap_or(key));
+        }
+        let mut update = doc! { "$set": &fields };
+        if !on_insert.is_empty() {
+            update.insert("$setOnInsert", on_insert);
+        }
+
+        self.settings
+            .update_one(doc! { "group_id": group_id }, update, UpdateOptions::builder().upsert(true).build())
+            .await?;
+        Ok(())
+    }
+
+    async fn add_strike(&self, group_id: i64, user_id: i64) -> DbResult<i64> {
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let record = self.strikes
+            .find_one_and_update(
+                doc! { "group_id": group_id, "user_id": user_id },
+                doc! { "$inc": { "count": 1_i64 } },
+                options,
+            )
+            .await?;
+
+        Ok(record.map_or(1, |r| r.count))
+    }
+
+    async fn reset_strikes(&self, group_id: i64, user_id: i64) -> DbResult<()> {
+        self.strikes
+            .delete_one(doc! { "group_id": group_id, "user_id": user_id }, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn add_keyword(&self, kind: ListKind, group_id: i64, keyword: &str) -> DbResult<bool> {
+        upsert_keyword(&self.list_collection(kind), doc! { "group_id": group_id, "keyword": keyword }).await
+    }
+
+    async fn add_keywords(&self, kind: ListKind, group_id: i64, keywords: Vec<String>) -> DbResult<()> {
+        // Unordered: duplikat yang ditolak unique index tidak menghentikan sisanya
+        let docs = keywords.into_iter().map(|keyword| doc! { "group_id": group_id, "keyword": keyword });
+        ignore_duplicates(self.list_collection(kind).insert_many(docs, unordered_insert()).await)
+    }
+
+    async fn remove_keyword(&self, kind: ListKind, group_id: i64, keyword: &str) -> DbResult<bool> {
+        let result = self.list_collection(kind)
+            .delete_many(doc! { "group_id": group_id, "keyword": keyword }, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn clear_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<u64> {
+        let result = self.list_collection(kind)
+            .delete_many(doc! { "group_id": group_id }, None)
+            .await?;
+        Ok(result.deleted_count)
+    }
+
+    async fn list_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<Vec<String>> {
+        // Load from database with optimized query
+        let find_options = FindOptions::builder()
+            .projection(doc! { "keyword": 1, "_id": 0 })
+            .build();
+
+        let cursor = self.list_collection(kind)
+            .find(doc! { "group_id": group_id }, find_options)
+            .await?;
+
+        let docs = collect(cursor).await?;
+        Ok(docs.iter().filter_map(|d| d.get_str("keyword").ok().map(str::to_string)).collect())
+    }
+
+    async fn add_global_keyword(&self, keyword: &str) -> DbResult<bool> {
+        upsert_keyword(&self.global_keywords, doc! { "keyword": keyword }).await
+    }
+
+    async fn remove_global_keyword(&self, keyword: &str) -> DbResult<bool> {
+        let result = self.global_keywords
+            .delete_many(doc! { "keyword": keyword }, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn list_global_keywords(&self) -> DbResult<Vec<String>> {
+        let cursor = self.global_keywords.find(None, None).await?;
+        Ok(collect(cursor).await?.into_iter().map(|item| item.keyword).collect())
+    }
+
+    async fn train_classifier(&self, scope: i64, tokens: &[String], is_spam: bool) -> DbResult<()> {
+        let field = if is_spam { "spam" } else { "ham" };
+        let upsert = UpdateOptions::builder().upsert(true).build();
+
+        self.bayes_totals
+            .update_one(
+                doc! { "scope": scope },
+                doc! { "$inc": { field: 1_i64 } },
+                upsert.clone(),
+            )
+            .await?;
+
+        let now = DateTime::now();
+        try_join_all(tokens.iter().map(|token| {
+            self.bayes_tokens.update_one(
+                doc! { "scope": scope, "token": token },
+                doc! { "$inc": { field: 1_i64 }, "$set": { "updated_at": now } },
+                upsert.clone(),
+            )
+        }))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load_classifier(&self, scope: i64) -> DbResult<BayesModel> {
+        let mut model = BayesModel::default();
+
+        if let Some(totals) = self.bayes_totals.find_one(doc! { "scope": scope }, None).await? {
+            model.spam_docs = totals.spam;
+            model.ham_docs = totals.ham;
+        }
+
+        // Model yang belum cukup dilatih tidak perlu memuat token sama sekali
+        if model.is_trained() {
+            let pipeline = [
+                doc! { "$match": { "scope": scope } },
+                doc! { "$addFields": { "total": { "$add": [{ "$ifNull": ["$spam", 0] }, { "$ifNull": ["$ham", 0] }] } } },
+                doc! { "$sort": { "total": -1 } },
+                doc! { "$limit": classifier::MAX_VOCABULARY as i64 },
+            ];
+            let cursor = self.bayes_tokens.aggregate(pipeline, None).await?;
+            for document in collect(cursor).await? {
+                let stat: TokenStat = bson::from_document(document)?;
+                model.tokens.insert(stat.token, (stat.spam, stat.ham));
+            }
+        }
+
+        Ok(model)
+    }
+
+    async fn prune_classifier(&self, cutoff: DateTime) -> DbResult<u64> {
+        // Token dari sebelum updated_at dicatat dianggap sudah lama
+        let filter = doc! {
+            "updated_at": { "$not": { "$gte": cutoff } },
+            "$expr": { "$lte": [{ "$add": [{ "$ifNull": ["$spam", 0] }, { "$ifNull": ["$ham", 0] }] }, 1] },
+        };
+        Ok(self.bayes_tokens.delete_many(filter, None).await?.deleted_count)
+    }
+
+    async fn record_example(&self, example: SpamExample) -> DbResult<bool> {
+        let Some(message_id) = example.message_id else {
+            self.examples.insert_one(example, None).await?;
+            return Ok(true);
+        };
+
+        let filter = doc! { "group_id": example.group_id, "message_id": message_id };
+        let update = doc! { "$setOnInsert": bson::to_document(&example)? };
+        match self.examples.update_one(filter, update, UpdateOptions::builder().upsert(true).build()).await {
+            Ok(result) => Ok(result.upserted_id.is_some()),
+            // Laporan bersamaan untuk pesan yang sama ditolak unique index
+            Err(e) if matches!(e.kind.as_ref(), ErrorKind::Write(WriteFailure::WriteError(w)) if w.code == 11000) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list_ham_texts(&self, group_id: i64) -> DbResult<Vec<String>> {
+        let find_options = FindOptions::builder()
+            .projection(doc! { "text": 1, "_id": 0 })
+            .build();
+
+        // Projection hanya mengambil text, jadi baca sebagai Document
+        let cursor = self.examples
+            .clone_with_type::<Document>()
+            .find(doc! { "group_id": group_id, "is_spam": false }, find_options)
+            .await?;
+
+        let docs = collect(cursor).await?;
+        Ok(docs.iter().filter_map(|d| d.get_str("text").ok().map(str::to_string)).collect())
+    }
+
+    async fn track_group(&self, group_id: i64, title: &str) -> DbResult<()> {
+        self.groups
+            .update_one(
+                doc! { "group_id": group_id },
+                doc! { "$set": { "title": title, "updated_at": DateTime::now() } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn forget_group(&self, group_id: i64) -> DbResult<()> {
+        self.groups.delete_one(doc! { "group_id": group_id }, None).await?;
+        Ok(())
+    }
+
+    async fn list_groups(&self) -> DbResult<Vec<KnownGroup>> {
+        let cursor = self.groups.find(None, None).await?;
+        collect(cursor).await
+    }
+
+    async fn add_global_ban(&self, user_id: i64, reason: &str, banned_by: i64) -> DbResult<()> {
+        self.global_bans
+            .update_one(
+                doc! { "user_id": user_id },
+                doc! { "$set": { "reason": reason, "banned_by": banned_by, "created_at": DateTime::now() } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_global_ban(&self, user_id: i64) -> DbResult<()> {
+        self.global_bans.delete_one(doc! { "user_id": user_id }, None).await?;
+        Ok(())
+    }
+
+    async fn list_global_bans(&self) -> DbResult<HashSet<i64>> {
+        Self::fetch_user_ids(&self.global_bans, doc! {}).await
+    }
+
+    async fn create_federation(&self, federation: &Federation) -> DbResult<()> {
+        self.federations.insert_one(federation, None).await?;
+        Ok(())
+    }
+
+    async fn get_federation(&self, fed_id: &str) -> DbResult<Option<Federation>> {
+        Ok(self.federations.find_one(doc! { "fed_id": fed_id }, None).await?)
+    }
+
+    async fn federation_of(&self, group_id: i64) -> DbResult<Option<Federation>> {
+        Ok(self.federations.find_one(doc! { "groups": group_id }, None).await?)
+    }
+
+    async fn add_federation_group(&self, fed_id: &str, group_id: i64) -> DbResult<()> {
+        self.federations
+            .update_one(doc! { "fed_id": fed_id }, doc! { "$addToSet": { "groups": group_id } }, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_federation_group(&self, group_id: i64) -> DbResult<()> {
+        self.federations
+            .update_many(doc! { "groups": group_id }, doc! { "$pull": { "groups": group_id } }, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_fed_share_blacklist(&self, fed_id: &str, share: bool, source: i64) -> DbResult<()> {
+        self.federations
+            .update_one(
+                doc! { "fed_id": fed_id },
+                doc! { "$set": { "share_blacklist": share, "blacklist_source": source } },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn create_fed_invite(&self, token: &str, fed_id: &str, expires_at: DateTime) -> DbResult<()> {
+        self.fed_invites
+            .insert_one(doc! { "_id": token, "fed_id": fed_id, "expires_at": expires_at }, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn take_fed_invite(&self, token: &str) -> DbResult<Option<String>> {
+        // Hapus dan baca dalam satu operasi supaya token tidak bisa dipakai dua kali
+        let invite = self
+            .fed_invites
+            .find_one_and_delete(doc! { "_id": token, "expires_at": { "$gt": DateTime::now() } }, None)
+            .await?;
+        Ok(invite.and_then(|d| d.get_str("fed_id").ok().map(str::to_string)))
+    }
+
+    async fn add_fed_ban(&self, fed_id: &str, user_id: i64, reason: &str, banned_by: i64) -> DbResult<()> {
+        self.fed_bans
+            .update_one(
+                doc! { "fed_id": fed_id, "user_id": user_id },
+                doc! { "$set": { "reason": reason, "banned_by": banned_by, "created_at": DateTime::now() } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_fed_ban(&self, fed_id: &str, user_id: i64) -> DbResult<()> {
+        self.fed_bans
+            .delete_one(doc! { "fed_id": fed_id, "user_id": user_id }, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_fed_bans(&self, fed_id: &str) -> DbResult<HashSet<i64>> {
+        Self::fetch_user_ids(&self.fed_bans, doc! { "fed_id": fed_id }).await
+    }
+
+    async fn set_connection(&self, user_id: i64, group_id: i64) -> DbResult<()> {
+        self.connections
+            .update_one(
+                doc! { "user_id": user_id },
+                doc! { "$set": { "group_id": group_id } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn clear_connection(&self, user_id: i64) -> DbResult<()> {
+        self.connections.delete_one(doc! { "user_id": user_id }, None).await?;
+        Ok(())
+    }
+
+    async fn get_connection(&self, user_id: i64) -> DbResult<Option<i64>> {
+        let connection = self.connections.find_one(doc! { "user_id": user_id }, None).await?;
+        Ok(connection.map(|c| c.group_id))
+    }
+
+    async fn save_template(&self, template: ConfigTemplate) -> DbResult<()> {
+        self.templates
+            .replace_one(
+                doc! { "owner_id": template.owner_id, "name": &template.name },
+                &template,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_template(&self, owner_id: i64, name: &str) -> DbResult<Option<ConfigTemplate>> {
+        Ok(self.templates.find_one(doc! { "owner_id": owner_id, "name": name }, None).await?)
+    }
+
+    async fn delete_template(&self, owner_id: i64, name: &str) -> DbResult<()> {
+        self.templates.delete_one(doc! { "owner_id": owner_id, "name": name }, None).await?;
+        Ok(())
+    }
+
+    async fn list_templates(&self, owner_id: i64) -> DbResult<Vec<ConfigTemplate>> {
+        let cursor = self.templates.find(doc! { "owner_id": owner_id }, None).await?;
+        collect(cursor).await
+    }
+
+    async fn has_marker(&self, name: &str) -> DbResult<bool> {
+        Ok(self.markers.find_one(doc! { "_id": name }, None).await?.is_some())
+    }
+
+    async fn set_marker(&self, name: &str) -> DbResult<()> {
+        self.markers
+            .update_one(
+                doc! { "_id": name },
+                doc! { "$setOnInsert": { "created_at": DateTime::now() } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> DbResult<()> {
+        self.db.run_command(doc! { "ping": 1 }, None).await?;
+        Ok(())
+    }
+
+    async fn migrate(&self) -> DbResult<MigrationReport> {
+        migrations::run(self).await
+    }
+
+    async fn publish_change(&self, change: &Invalidation) -> DbResult<()> {
+        self.revisions
+            .update_one(
+                doc! { "_id": change.collection() },
+                doc! { "$inc": { "rev": 1_i64 } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn watch_changes(&self, mode: SyncMode, tx: UnboundedSender<Invalidation>) -> DbResult<()> {
+        match mode {
+            SyncMode::Off => Ok(()),
+            SyncMode::Poll => self.poll_revisions(&tx).await,
+            SyncMode::ChangeStream => match self.watch_change_stream(&tx).await {
+                Err(DbError::Mongo(e))
+                    if matches!(e.kind.as_ref(), ErrorKind::Command(c) if c.code == CHANGE_STREAM_UNSUPPORTED) =>
+                {
+                    log::warn!("Change stream tidak didukung (bukan replica set), beralih ke polling");
+                    self.poll_revisions(&tx).await
+                }
+                result => result,
+            },
+        }
+    }
+}
@@ -0,0 +1,874 @@
+use async_trait::async_trait;
+use mongodb::bson::{self, DateTime, Document};
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashSet;
+use std::sync::Arc;
+use crate::classifier::{self, BayesModel};
+use crate::error::{DbError, DbResult};
+use crate::models::{upgrade_settings, ConfigTemplate, Federation, GroupSettings, KnownGroup, SpamExample};
+use super::{apply_settings_fields, ListKind, Store};
+
+// Settings dan snapshot template disimpan sebagai JSON supaya skema tabel
+// tidak perlu ikut berubah setiap ada field baru
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS settings (group_id INTEGER PRIMARY KEY, data TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS strikes (
+    group_id INTEGER NOT NULL, user_id INTEGER NOT NULL, count INTEGER NOT NULL,
+    PRIMARY KEY (group_id, user_id)
+);
+CREATE TABLE IF NOT EXISTS blacklist (group_id INTEGER NOT NULL, keyword TEXT NOT NULL, PRIMARY KEY (group_id, keyword));
+CREATE TABLE IF NOT EXISTS whitelist (group_id INTEGER NOT NULL, keyword TEXT NOT NULL, PRIMARY KEY (group_id, keyword));
+CREATE TABLE IF NOT EXISTS global_keywords (keyword TEXT PRIMARY KEY);
+CREATE TABLE IF NOT EXISTS bayes_totals (scope INTEGER PRIMARY KEY, spam INTEGER NOT NULL DEFAULT 0, ham INTEGER NOT NULL DEFAULT 0);
+CREATE TABLE IF NOT EXISTS bayes_tokens (
+    scope INTEGER NOT NULL, token TEXT NOT NULL, spam INTEGER NOT NULL DEFAULT 0, ham INTEGER NOT NULL DEFAULT 0,
+    updated_at INTEGER NOT NULL DEFAULT 0, PRIMARY KEY (scope, token)
+);
+CREATE TABLE IF NOT EXISTS examples (
+    id INTEGER PRIMARY KEY AUTOINCREMENT, group_id INTEGER NOT NULL, sender_id INTEGER, message_id INTEGER,
+    reported_by INTEGER NOT NULL, text TEXT NOT NULL, is_spam INTEGER NOT NULL, created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS examples_group ON examples (group_id, is_spam);
+CREATE TABLE IF NOT EXISTS groups (group_id INTEGER PRIMARY KEY, title TEXT NOT NULL, updated_at INTEGER NOT NULL);
+CREATE TABLE IF NOT EXISTS global_bans (
+    user_id INTEGER PRIMARY KEY, reason TEXT NOT NULL, banned_by INTEGER NOT NULL, created_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS federations (
+    fed_id TEXT PRIMARY KEY, name TEXT NOT NULL, owner_id INTEGER NOT NULL, share_blacklist INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE IF NOT EXISTS federation_groups (group_id INTEGER PRIMARY KEY, fed_id TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS fed_bans (
+    fed_id TEXT NOT NULL, user_id INTEGER NOT NULL, reason TEXT NOT NULL, banned_by INTEGER NOT NULL,
+    created_at INTEGER NOT NULL, PRIMARY KEY (fed_id, user_id)
+);
+CREATE TABLE IF NOT EXISTS connections (user_id INTEGER PRIMARY KEY, group_id INTEGER NOT NULL);
+CREATE TABLE IF NOT EXISTS templates (
+    name TEXT NOT NULL, owner_id INTEGER NOT NULL, snapshot TEXT NOT NULL, updated_at INTEGER NOT NULL,
+    PRIMARY KEY (owner_id, name)
+);
+CREATE TABLE IF NOT EXISTS fed_invites (token TEXT PRIMARY KEY, fed_id TEXT NOT NULL, expires_at INTEGER NOT NULL);
+CREATE TABLE IF NOT EXISTS markers (name TEXT PRIMARY KEY, created_at INTEGER NOT NULL);
+";
+
+// Kolom yang ditambahkan setelah SCHEMA pertama kali dipakai. File lama tidak
+// ikut berubah oleh CREATE TABLE IF NOT EXISTS, jadi kolomnya ditambahkan di sini.
+const ADDED_COLUMNS: [(&str, &str, &str); 3] = [
+    ("bayes_tokens", "updated_at", "INTEGER NOT NULL DEFAULT 0"),
+    ("examples", "message_id", "INTEGER"),
+    ("federations", "blacklist_source", "INTEGER"),
+];
+
+fn add_missing_columns(conn: &Connection) -> DbResult<()> {
+    for (table, column, definition) in ADDED_COLUMNS {
+        let mut stmt = conn.prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))?;
+        let columns = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        if !columns.iter().any(|c| c == column) {
+            conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+        }
+    }
+    // Satu pesan hanya dicatat sekali; contoh lama tanpa message_id (NULL) tidak bentrok
+    conn.execute_batch("CREATE UNIQUE INDEX IF NOT EXISTS examples_message ON examples (group_id, message_id);")?;
+    Ok(())
+}
+
+// File lama memakai name sebagai primary key (satu namespace global). Tabel
+// dibangun ulang dengan key (owner_id, name); baris lama tetap milik pembuatnya.
+fn rekey_templates(conn: &Connection) -> DbResult<()> {
+    let owner_in_key: bool = conn.query_row(
+        "SELECT pk > 0 FROM pragma_table_info('templates') WHERE name = 'owner_id'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !owner_in_key {
+        conn.execute_batch(
+            "BEGIN;
+             ALTER TABLE templates RENAME TO templates_old;
+             CREATE TABLE templates (
+                 name TEXT NOT NULL, owner_id INTEGER NOT NULL, snapshot TEXT NOT NULL, updated_at INTEGER NOT NULL,
+                 PRIMARY KEY (owner_id, name)
+             );
+             INSERT INTO templates SELECT name, owner_id, snapshot, updated_at FROM templates_old;
+             DROP TABLE templates_old;
+             COMMIT;",
+        )?;
+    }
+    Ok(())
+}
+
+fn now_millis() -> i64 {
+    DateTime::now().timestamp_millis()
+}
+
+fn read_settings(conn: &Connection, group_id: i64) -> DbResult<Option<GroupSettings>> {
+    let data: Option<String> = conn
+        .query_row("SELECT data FROM settings WHERE group_id = ?1", [group_id], |row| row.get(0))
+        .optional()?;
+    let Some(data) = data else {
+        return Ok(None);
+    };
+
+    // Dokumen skema lama di-upgrade saat dibaca lalu ditulis ulang
+    let mut document = bson::to_document(&serde_json::from_str::<serde_json::Value>(&data)?)?;
+    let upgraded = upgrade_settings(&mut document);
+    let settings: GroupSettings = bson::from_document(document)?;
+    if upgraded {
+        write_settings(conn, &settings)?;
+    }
+    Ok(Some(settings))
+}
+
+fn write_settings(conn: &Connection, settings: &GroupSettings) -> DbResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (group_id, data) VALUES (?1, ?2)",
+        params![settings.group_id, serde_json::to_string(settings)?],
+    )?;
+    Ok(())
+}
+
+fn read_federation(conn: &Connection, fed_id: &str) -> DbResult<Option<Federation>> {
+    let federation = conn
+        .query_row(
+            "SELECT name, owner_id, share_blacklist, blacklist_source FROM federations WHERE fed_id = ?1",
+            [fed_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, bool>(2)?,
+                    row.get::<_, Option<i64>>(3)?,
+                ))
+            },
+        )
+        .optional()?;
+
+    let Some((name, owner_id, share_blacklist, blacklist_source)) = federation else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn.prepare("SELECT group_id FROM federation_groups WHERE fed_id = ?1")?;
+    let groups = stmt.query_map([fed_id], |row| row.get(0))?.collect::<Result<Vec<i64>, _>>()?;
+
+    Ok(Some(Federation {
+        id: None,
+        fed_id: fed_id.to_string(),
+        name,
+        owner_id,
+        groups,
+        share_blacklist,
+        blacklist_source,
+    }))
+}
+
+fn read_template(row: &rusqlite::Row) -> rusqlite::Result<(String, i64, String, i64)> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+}
+
+fn to_template((name, owner_id, snapshot, updated_at): (String, i64, String, i64)) -> DbResult<ConfigTemplate> {
+    Ok(ConfigTemplate {
+        id: None,
+        name,
+        owner_id,
+        snapshot: serde_json::from_str(&snapshot)?,
+        updated_at: DateTime::from_millis(updated_at),
+    })
+}
+
+// Backend satu file untuk deployment kecil tanpa server MongoDB.
+// rusqlite bersifat blocking, jadi setiap query dijalankan lewat spawn_blocking.
+#[derive(Clone)]
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> DbResult<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+        Self::with_connection(conn)
+    }
+
+    // Buat skema yang belum ada dan upgrade tabel dari versi lama
+    fn with_connection(conn: Connection) -> DbResult<Self> {
+        conn.execute_batch(SCHEMA)?;
+        add_missing_columns(&conn)?;
+        rekey_templates(&conn)?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    async fn call<T, F>(&self, f: F) -> DbResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> DbResult<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || f(&mut conn.lock()))
+            .await
+            .map_err(|e| DbError::Backend(e.to_string()))?
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn get_settings(&self, group_id: i64) -> DbResult<Option<GroupSettings>> {
+        self.call(move |conn| read_settings(conn, group_id)).await
+    }
+
+    async fn update_settings(&self, group_id: i64, fields: Document) -> DbResult<()> {
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let current = read_settings(&tx, group_id)?.unwrap_or_else(|| GroupSettings::new(group_id));
+            write_settings(&tx, &apply_settings_fields(current, &fields)?)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn add_strike(&self, group_id: i64, user_id: i64) -> DbResult<i64> {
+        self.call(move |conn| {
+            Ok(conn.query_row(
+                "INSERT INTO strikes (group_id, user_id, count) VALUES (?1, ?2, 1)
+                 ON CONFLICT (group_id, user_id) DO UPDATE SET count = count + 1
+                 RETURNING count",
+                params![group_id, user_id],
+                |row| row.get(0),
+            )?)
+        })
+        .await
+    }
+
+    async fn reset_strikes(&self, group_id: i64, user_id: i64) -> DbResult<()> {
+        self.call(move |conn| {
+            conn.execute("DELETE FROM strikes WHERE group_id = ?1 AND user_id = ?2", params![group_id, user_id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn add_keyword(&self, kind: ListKind, group_id: i64, keyword: &str) -> DbResult<bool> {
+        let keyword = keyword.to_string();
+        self.call(move |conn| {
+            let sql = format!("INSERT OR IGNORE INTO {} (group_id, keyword) VALUES (?1, ?2)", kind.as_str());
+            Ok(conn.execute(&sql, params![group_id, keyword])? > 0)
+        })
+        .await
+    }
+
+    async fn add_keywords(&self, kind: ListKind, group_id: i64, keywords: Vec<String>) -> DbResult<()> {
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            {
+                let sql = format!("INSERT OR IGNORE INTO {} (group_id, keyword) VALUES (?1, ?2)", kind.as_str());
+                let mut stmt = tx.prepare(&sql)?;
+                for keyword in &keywords {
+                    stmt.execute(params![group_id, keyword])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_keyword(&self, kind: ListKind, group_id: i64, keyword: &str) -> DbResult<bool> {
+        let keyword = keyword.to_string();
+        self.call(move |conn| {
+            let sql = format!("DELETE FROM {} WHERE group_id = ?1 AND keyword = ?2", kind.as_str());
+            Ok(conn.execute(&sql, params![group_id, keyword])? > 0)
+        })
+        .await
+    }
+
+    async fn clear_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<u64> {
+        self.call(move |conn| {
+            let sql = format!("DELETE FROM {} WHERE group_id = ?1", kind.as_str());
+            Ok(conn.execute(&sql, [group_id])? as u64)
+        })
+        .await
+    }
+
+    async fn list_keywords(&self, kind: ListKind, group_id: i64) -> DbResult<Vec<String>> {
+        self.call(move |conn| {
+            let sql = format!("SELECT keyword FROM {} WHERE group_id = ?1", kind.as_str());
+            let mut stmt = conn.prepare(&sql)?;
+            let keywords = stmt.query_map([group_id], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            Ok(keywords)
+        })
+        .await
+    }
+
+    async fn add_global_keyword(&self, keyword: &str) -> DbResult<bool> {
+        let keyword = keyword.to_string();
+        self.call(move |conn| Ok(conn.execute("INSERT OR IGNORE INTO global_keywords (keyword) VALUES (?1)", [keyword])? > 0))
+            .await
+    }
+
+    async fn remove_global_keyword(&self, keyword: &str) -> DbResult<bool> {
+        let keyword = keyword.to_string();
+        self.call(move |conn| Ok(conn.execute("DELETE FROM global_keywords WHERE keyword = ?1", [keyword])? > 0))
+            .await
+    }
+
+    async fn list_global_keywords(&self) -> DbResult<Vec<String>> {
+        self.call(|conn| {
+            let mut stmt = conn.prepare("SELECT keyword FROM global_keywords")?;
+            let keywords = stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            Ok(keywords)
+        })
+        .await
+    }
+
+    async fn train_classifier(&self, scope: i64, tokens: &[String], is_spam: bool) -> DbResult<()> {
+        let tokens = tokens.to_vec();
+        let (spam, ham) = if is_spam { (1_i64, 0_i64) } else { (0, 1) };
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO bayes_totals (scope, spam, ham) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (scope) DO UPDATE SET spam = spam + ?2, ham = ham + ?3",
+                params![scope, spam, ham],
+            )?;
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT INTO bayes_tokens (scope, token, spam, ham, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)
+                     ON CONFLICT (scope, token) DO UPDATE SET spam = spam + ?3, ham = ham + ?4, updated_at = ?5",
+                )?;
+                let now = now_millis();
+                for token in &tokens {
+                    stmt.execute(params![scope, token, spam, ham, now])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_classifier(&self, scope: i64) -> DbResult<BayesModel> {
+        self.call(move |conn| {
+            let mut model = BayesModel::default();
+
+            if let Some((spam, ham)) = conn
+                .query_row("SELECT spam, ham FROM bayes_totals WHERE scope = ?1", [scope], |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()?
+            {
+                model.spam_docs = spam;
+                model.ham_docs = ham;
+            }
+
+            // Model yang belum cukup dilatih tidak perlu memuat token sama sekali
+            if model.is_trained() {
+                let mut stmt = conn.prepare(
+                    "SELECT token, spam, ham FROM bayes_tokens WHERE scope = ?1 ORDER BY spam + ham DESC LIMIT ?2",
+                )?;
+                let rows = stmt.query_map(params![scope, classifier::MAX_VOCABULARY as i64], |row| Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?))))?;
+                for row in rows {
+                    let (token, counts) = row?;
+                    model.tokens.insert(token, counts);
+                }
+            }
+
+            Ok(model)
+        })
+        .await
+    }
+
+    async fn prune_classifier(&self, cutoff: DateTime) -> DbResult<u64> {
+        self.call(move |conn| {
+            let removed = conn.execute(
+                "DELETE FROM bayes_tokens WHERE spam + ham <= 1 AND updated_at < ?1",
+                [cutoff.timestamp_millis()],
+            )?;
+            Ok(removed as u64)
+        })
+        .await
+    }
+
+    async fn record_example(&self, example: SpamExample) -> DbResult<bool> {
+        self.call(move |conn| {
+            let inserted = conn.execute(
+                "INSERT OR IGNORE INTO examples (group_id, sender_id, message_id, reported_by, text, is_spam, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    example.group_id,
+                    example.sender_id,
+                    example.message_id,
+                    example.reported_by,
+                    example.text,
+                    example.is_spam,
+                    example.created_at.timestamp_millis()
+                ],
+            )?;
+            Ok(inserted > 0)
+        })
+        .await
+    }
+
+    async fn list_ham_texts(&self, group_id: i64) -> DbResult<Vec<String>> {
+        self.call(move |conn| {
+            let mut stmt = conn.prepare("SELECT text FROM examples WHERE group_id = ?1 AND is_spam = 0")?;
+            let texts = stmt.query_map([group_id], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            Ok(texts)
+        })
+        .await
+    }
+
+    async fn track_group(&self, group_id: i64, title: &str) -> DbResult<()> {
+        let title = title.to_string();
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO groups (group_id, title, updated_at) VALUES (?1, ?2, ?3)",
+                params![group_id, title, now_millis()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn forget_group(&self, group_id: i64) -> DbResult<()> {
+        self.call(move |conn| {
+            conn.execute("DELETE FROM groups WHERE group_id = ?1", [group_id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_groups(&self) -> DbResult<Vec<KnownGroup>> {
+        self.call(|conn| {
+            let mut stmt = conn.prepare("SELECT group_id, title, updated_at FROM groups")?;
+            let groups = stmt
+                .query_map([], |row| {
+                    Ok(KnownGroup {
+                        id: None,
+                        group_id: row.get(0)?,
+                        title: row.get(1)?,
+                        updated_at: DateTime::from_millis(row.get(2)?),
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+            Ok(groups)
+        })
+        .await
+    }
+
+    async fn add_global_ban(&self, user_id: i64, reason: &str, banned_by: i64) -> DbResult<()> {
+        let reason = reason.to_string();
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO global_bans (user_id, reason, banned_by, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![user_id, reason, banned_by, now_millis()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_global_ban(&self, user_id: i64) -> DbResult<()> {
+        self.call(move |conn| {
+            conn.execute("DELETE FROM global_bans WHERE user_id = ?1", [user_id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_global_bans(&self) -> DbResult<HashSet<i64>> {
+        self.call(|conn| {
+            let mut stmt = conn.prepare("SELECT user_id FROM global_bans")?;
+            let users = stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            Ok(users)
+        })
+        .await
+    }
+
+    async fn create_federation(&self, federation: &Federation) -> DbResult<()> {
+        let federation = federation.clone();
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT INTO federations (fed_id, name, owner_id, share_blacklist, blacklist_source) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    federation.fed_id,
+                    federation.name,
+                    federation.owner_id,
+                    federation.share_blacklist,
+                    federation.blacklist_source
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_federation(&self, fed_id: &str) -> DbResult<Option<Federation>> {
+        let fed_id = fed_id.to_string();
+        self.call(move |conn| read_federation(conn, &fed_id)).await
+    }
+
+    async fn federation_of(&self, group_id: i64) -> DbResult<Option<Federation>> {
+        self.call(move |conn| {
+            let fed_id: Option<String> = conn
+                .query_row("SELECT fed_id FROM federation_groups WHERE group_id = ?1", [group_id], |row| row.get(0))
+                .optional()?;
+            match fed_id {
+                Some(fed_id) => read_federation(conn, &fed_id),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    async fn add_federation_group(&self, fed_id: &str, group_id: i64) -> DbResult<()> {
+        let fed_id = fed_id.to_string();
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO federation_groups (group_id, fed_id)
+                 SELECT ?1, fed_id FROM federations WHERE fed_id = ?2",
+                params![group_id, fed_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_federation_group(&self, group_id: i64) -> DbResult<()> {
+        self.call(move |conn| {
+            conn.execute("DELETE FROM federation_groups WHERE group_id = ?1", [group_id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn set_fed_share_blacklist(&self, fed_id: &str, share: bool, source: i64) -> DbResult<()> {
+        let fed_id = fed_id.to_string();
+        self.call(move |conn| {
+            conn.execute(
+                "UPDATE federations SET share_blacklist = ?1, blacklist_source = ?2 WHERE fed_id = ?3",
+                params![share, source, fed_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn create_fed_invite(&self, token: &str, fed_id: &str, expires_at: DateTime) -> DbResult<()> {
+        let (token, fed_id) = (token.to_string(), fed_id.to_string());
+        self.call(move |conn| {
+            // Bersihkan undangan kedaluwarsa sekalian
+            conn.execute("DELETE FROM fed_invites WHERE expires_at <= ?1", [now_millis()])?;
+            conn.execute(
+                "INSERT INTO fed_invites (token, fed_id, expires_at) VALUES (?1, ?2, ?3)",
+                params![token, fed_id, expires_at.timestamp_millis()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn take_fed_invite(&self, token: &str) -> DbResult<Option<String>> {
+        let token = token.to_string();
+        self.call(move |conn| {
+            let invite: Option<(String, i64)> = conn
+                .query_row(
+                    "DELETE FROM fed_invites WHERE token = ?1 RETURNING fed_id, expires_at",
+                    [token],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            Ok(invite.filter(|&(_, expires)| expires > now_millis()).map(|(fed_id, _)| fed_id))
+        })
+        .await
+    }
+
+    async fn add_fed_ban(&self, fed_id: &str, user_id: i64, reason: &str, banned_by: i64) -> DbResult<()> {
+        let (fed_id, reason) = (fed_id.to_string(), reason.to_string());
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO fed_bans (fed_id, user_id, reason, banned_by, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![fed_id, user_id, reason, banned_by, now_millis()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_fed_ban(&self, fed_id: &str, user_id: i64) -> DbResult<()> {
+        let fed_id = fed_id.to_string();
+        self.call(move |conn| {
+            conn.execute("DELETE FROM fed_bans WHERE fed_id = ?1 AND user_id = ?2", params![fed_id, user_id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_fed_bans(&self, fed_id: &str) -> DbResult<HashSet<i64>> {
+        let fed_id = fed_id.to_string();
+        self.call(move |conn| {
+            let mut stmt = conn.prepare("SELECT user_id FROM fed_bans WHERE fed_id = ?1")?;
+            let users = stmt.query_map([fed_id], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            Ok(users)
+        })
+        .await
+    }
+
+    async fn set_connection(&self, user_id: i64, group_id: i64) -> DbResult<()> {
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO connections (user_id, group_id) VALUES (?1, ?2)",
+                params![user_id, group_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn clear_connection(&self, user_id: i64) -> DbResult<()> {
+        self.call(move |conn| {
+            conn.execute("DELETE FROM connections WHERE user_id = ?1", [user_id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_connection(&self, user_id: i64) -> DbResult<Option<i64>> {
+        self.call(move |conn| {
+            Ok(conn
+                .query_row("SELECT group_id FROM connections WHERE user_id = ?1", [user_id], |row| row.get(0))
+                .optional()?)
+        })
+        .await
+    }
+
+    async fn save_template(&self, template: ConfigTemplate) -> DbResult<()> {
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO templates (name, owner_id, snapshot, updated_at) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    template.name,
+                    template.owner_id,
+                    serde_json::to_string(&template.snapshot)?,
+                    template.updated_at.timestamp_millis()
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_template(&self, owner_id: i64, name: &str) -> DbResult<Option<ConfigTemplate>> {
+        let name = name.to_string();
+        self.call(move |conn| {
+            let row = conn
+                .query_row(
+                    "SELECT name, owner_id, snapshot, updated_at FROM templates WHERE owner_id = ?1 AND name = ?2",
+                    params![owner_id, name],
+                    read_template,
+                )
+                .optional()?;
+            row.map(to_template).transpose()
+        })
+        .await
+    }
+
+    async fn delete_template(&self, owner_id: i64, name: &str) -> DbResult<()> {
+        let name = name.to_string();
+        self.call(move |conn| {
+            conn.execute("DELETE FROM templates WHERE owner_id = ?1 AND name = ?2", params![owner_id, name])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_templates(&self, owner_id: i64) -> DbResult<Vec<ConfigTemplate>> {
+        self.call(move |conn| {
+            let mut stmt = conn.prepare("SELECT name, owner_id, snapshot, updated_at FROM templates WHERE owner_id = ?1")?;
+            let rows = stmt.query_map([owner_id], read_template)?.collect::<Result<Vec<_>, _>>()?;
+            rows.into_iter().map(to_template).collect()
+        })
+        .await
+    }
+
+    async fn has_marker(&self, name: &str) -> DbResult<bool> {
+        let name = name.to_string();
+        self.call(move |conn| {
+            Ok(conn
+                .query_row("SELECT 1 FROM markers WHERE name = ?1", [name], |_| Ok(()))
+                .optional()?
+                .is_some())
+        })
+        .await
+    }
+
+    async fn set_marker(&self, name: &str) -> DbResult<()> {
+        let name = name.to_string();
+        self.call(move |conn| {
+            conn.execute("INSERT OR IGNORE INTO markers (name, created_at) VALUES (?1, ?2)", params![name, now_millis()])?;
+            Ok(())
+        })
+        .await
+    }
+
+    // File bisa terkunci atau disk penuh walau koneksi sudah terbuka
+    async fn ping(&self) -> DbResult<()> {
+        self.call(|conn| Ok(conn.query_row("SELECT 1", [], |_| Ok(()))?)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+    use crate::models::GroupMode;
+
+    fn memory_store() -> SqliteStore {
+        SqliteStore::open(":memory:").unwrap()
+    }
+
+    #[tokio::test]
+    async fn settings_round_trip() {
+        let store = memory_store();
+        assert!(store.get_settings(1).await.unwrap().is_none());
+
+        store
+            .update_settings(1, doc! { "mode": "observe", "strike_limit": 5_i64, "detectors.links": false })
+            .await
+            .unwrap();
+        let settings = store.get_settings(1).await.unwrap().unwrap();
+        assert_eq!(settings.group_id, 1);
+        assert_eq!(settings.mode, GroupMode::Observe);
+        assert_eq!(settings.strike_limit, 5);
+        assert!(!settings.detectors.links);
+        assert!(settings.detectors.keywords);
+
+        // Update berikutnya tidak menimpa field lain
+        store.update_settings(1, doc! { "language": "en" }).await.unwrap();
+        let settings = store.get_settings(1).await.unwrap().unwrap();
+        assert_eq!(settings.language, "en");
+        assert_eq!(settings.strike_limit, 5);
+    }
+
+    #[tokio::test]
+    async fn keywords_are_unique_per_group_and_list() {
+        let store = memory_store();
+        assert!(store.add_keyword(ListKind::Blacklist, 1, "promo").await.unwrap());
+        // Sudah ada
+        assert!(!store.add_keyword(ListKind::Blacklist, 1, "promo").await.unwrap());
+        // Grup dan daftar lain terpisah
+        assert!(store.add_keyword(ListKind::Blacklist, 2, "promo").await.unwrap());
+        assert!(store.add_keyword(ListKind::Whitelist, 1, "promo").await.unwrap());
+
+        store
+            .add_keywords(ListKind::Blacklist, 1, vec!["promo".to_string(), "vcs".to_string()])
+            .await
+            .unwrap();
+        let mut blacklist = store.list_keywords(ListKind::Blacklist, 1).await.unwrap();
+        blacklist.sort();
+        assert_eq!(blacklist, vec!["promo", "vcs"]);
+
+        assert!(store.remove_keyword(ListKind::Blacklist, 1, "vcs").await.unwrap());
+        // Tidak ditemukan
+        assert!(!store.remove_keyword(ListKind::Blacklist, 1, "vcs").await.unwrap());
+        assert_eq!(store.clear_keywords(ListKind::Blacklist, 1).await.unwrap(), 1);
+        assert_eq!(store.list_keywords(ListKind::Blacklist, 2).await.unwrap(), vec!["promo"]);
+
+        assert!(store.add_global_keyword("judi").await.unwrap());
+        assert!(!store.add_global_keyword("judi").await.unwrap());
+        assert!(store.remove_global_keyword("judi").await.unwrap());
+        assert!(!store.remove_global_keyword("judi").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn strikes_count_per_user_and_reset() {
+        let store = memory_store();
+        assert_eq!(store.add_strike(1, 10).await.unwrap(), 1);
+        assert_eq!(store.add_strike(1, 10).await.unwrap(), 2);
+        assert_eq!(store.add_strike(1, 11).await.unwrap(), 1);
+        assert_eq!(store.add_strike(2, 10).await.unwrap(), 1);
+
+        store.reset_strikes(1, 10).await.unwrap();
+        assert_eq!(store.add_strike(1, 10).await.unwrap(), 1);
+        assert_eq!(store.add_strike(1, 11).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn examples_are_recorded_once_per_message() {
+        let store = memory_store();
+        let example = |message_id: Option<i32>, is_spam: bool| SpamExample {
+            id: None,
+            group_id: 1,
+            sender_id: Some(10),
+            message_id,
+            reported_by: 20,
+            text: "rapat jam 9".to_string(),
+            is_spam,
+            created_at: DateTime::now(),
+        };
+        assert!(store.record_example(example(Some(5), false)).await.unwrap());
+        assert!(!store.record_example(example(Some(5), false)).await.unwrap());
+        // Contoh lama tanpa message_id tidak bentrok satu sama lain
+        assert!(store.record_example(example(None, false)).await.unwrap());
+        assert!(store.record_example(example(None, true)).await.unwrap());
+        assert_eq!(store.list_ham_texts(1).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn existing_rows_are_migrated_on_open() {
+        // Skema sebelum ADDED_COLUMNS dan sebelum template per owner
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE settings (group_id INTEGER PRIMARY KEY, data TEXT NOT NULL);
+             CREATE TABLE bayes_totals (scope INTEGER PRIMARY KEY, spam INTEGER NOT NULL DEFAULT 0, ham INTEGER NOT NULL DEFAULT 0);
+             CREATE TABLE bayes_tokens (
+                 scope INTEGER NOT NULL, token TEXT NOT NULL, spam INTEGER NOT NULL DEFAULT 0,
+                 ham INTEGER NOT NULL DEFAULT 0, PRIMARY KEY (scope, token)
+             );
+             CREATE TABLE examples (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT, group_id INTEGER NOT NULL, sender_id INTEGER,
+                 reported_by INTEGER NOT NULL, text TEXT NOT NULL, is_spam INTEGER NOT NULL, created_at INTEGER NOT NULL
+             );
+             CREATE TABLE federations (
+                 fed_id TEXT PRIMARY KEY, name TEXT NOT NULL, owner_id INTEGER NOT NULL,
+                 share_blacklist INTEGER NOT NULL DEFAULT 0
+             );
+             CREATE TABLE templates (name TEXT PRIMARY KEY, owner_id INTEGER NOT NULL, snapshot TEXT NOT NULL, updated_at INTEGER NOT NULL);
+             INSERT INTO settings VALUES (1, '{\"group_id\": 1, \"enabled\": true, \"observe\": true}');
+             INSERT INTO bayes_totals VALUES (1, 5, 5);
+             INSERT INTO bayes_tokens (scope, token, spam, ham) VALUES (1, 'promo', 3, 0);
+             INSERT INTO federations VALUES ('fed', 'Federasi', 7, 1);
+             INSERT INTO templates VALUES ('dasar', 7, '{\"blacklist\": [\"promo\"]}', 0);",
+        )
+        .unwrap();
+        let store = SqliteStore::with_connection(conn).unwrap();
+
+        // Settings v0 di-upgrade saat dibaca dan ditulis ulang
+        let settings = store.get_settings(1).await.unwrap().unwrap();
+        assert_eq!(settings.mode, GroupMode::Observe);
+        let stored: String = store
+            .call(|conn| Ok(conn.query_row("SELECT data FROM settings WHERE group_id = 1", [], |row| row.get(0))?))
+            .await
+            .unwrap();
+        assert!(!stored.contains("enabled"));
+
+        // Kolom baru ditambahkan dengan default, baris lama tetap terbaca
+        let model = store.load_classifier(1).await.unwrap();
+        assert_eq!(model.tokens.get("promo"), Some(&(3, 0)));
+        let federation = store.get_federation("fed").await.unwrap().unwrap();
+        assert!(federation.share_blacklist);
+        assert_eq!(federation.blacklist_source, None);
+
+        // Template lama tetap milik pembuatnya; owner lain boleh memakai nama yang sama
+        let template = store.get_template(7, "dasar").await.unwrap().unwrap();
+        assert_eq!(template.snapshot.blacklist, vec!["promo"]);
+        store
+            .save_template(ConfigTemplate {
+                id: None,
+                name: "dasar".to_string(),
+                owner_id: 8,
+                snapshot: Default::default(),
+                updated_at: DateTime::now(),
+            })
+            .await
+            .unwrap();
+        assert!(store.get_template(7, "dasar").await.unwrap().is_some());
+        assert_eq!(store.list_templates(8).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,555 @@
+use teloxide::net::Download;
+use teloxide::prelude::*;
+use teloxide::types::InputFile;
+use crate::database::Database;
+use crate::permissions::{self, Permission};
+use crate::error::{DbError, DbResult, HandlerResult};
+use crate::message::normalize_keyword;
+use crate::models::{
+    ActionPolicy, DetectorToggles, GroupSettings, GroupSnapshot, EMOJI_THRESHOLDS, NAME_SCORE_THRESHOLDS, SPAM_THRESHOLDS,
+    STRIKE_LIMITS,
+};
+use mongodb::bson::Document;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+// Batas validasi untuk file import
+const MAX_FILE_SIZE: u32 = 1024 * 1024;
+const MAX_KEYWORDS: usize = 1000;
+const MAX_KEYWORD_LEN: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+impl ImportMode {
+    pub fn parse(arg: &str, default: ImportMode) -> Option<Self> {
+        match arg.trim().to_lowercase().as_str() {
+            "" => Some(default),
+            "merge" => Some(Self::Merge),
+            "replace" => Some(Self::Replace),
+            _ => None,
+        }
+    }
+}
+
+// Isi file/template tidak valid, pengirim tidak berhak mengubah settings
+// di dalamnya, atau database gagal saat menerapkan
+pub enum ImportError {
+    Invalid(String),
+    Denied(Permission),
+    Db(DbError),
+}
+
+impl From<DbError> for ImportError {
+    fn from(e: DbError) -> Self {
+        ImportError::Db(e)
+    }
+}
+
+impl From<String> for ImportError {
+    fn from(e: String) -> Self {
+        ImportError::Invalid(e)
+    }
+}
+
+#[derive(Default)]
+pub struct ImportSummary {
+    pub blacklist_added: usize,
+    pub blacklist_removed: u64,
+    pub whitelist_added: usize,
+    pub whitelist_removed: u64,
+    pub settings_updated: bool,
+}
+
+impl ImportSummary {
+    pub fn describe(&self) -> String {
+        format!(
+            "import selesai.\nblacklist: +{} / -{}\nwhitelist: +{} / -{}\nsettings: {}",
+            self.blacklist_added,
+            self.blacklist_removed,
+            self.whitelist_added,
+            self.whitelist_removed,
+            if self.settings_updated { "diperbarui" } else { "tidak berubah" }
+        )
+    }
+}
+
+pub async fn snapshot(db: &Database, group_id: i64) -> DbResult<GroupSnapshot> {
+    let (settings, blacklist, whitelist) = tokio::join!(
+        db.get_settings(group_id),
+        db.list_blacklist(group_id),
+        db.list_whitelist(group_id)
+    );
+    let mut settings = settings?;
+    settings.id = None;
+
+    Ok(GroupSnapshot {
+        blacklist: blacklist?,
+        whitelist: whitelist?,
+        settings: serde_json::to_value(settings).ok(),
+    })
+}
+
+fn to_csv(snapshot: &GroupSnapshot) -> String {
+    let mut out = String::from("list,keyword\n");
+    for kw in &snapshot.blacklist {
+        out.push_str(&format!("blacklist,{}\n", kw));
+    }
+    for kw in &snapshot.whitelist {
+        out.push_str(&format!("whitelist,{}\n", kw));
+    }
+    out
+}
+
+// CSV hanya memuat daftar keyword; kolom kedua boleh berisi koma
+fn parse_csv(content: &str) -> Result<GroupSnapshot, String> {
+    let mut snapshot = GroupSnapshot::default();
+
+    for (line_no, line) in content.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        if line.is_empty() || line == "list,keyword" {
+            continue;
+        }
+        match line.split_once(',') {
+            Some(("blacklist", kw)) => snapshot.blacklist.push(kw.to_string()),
+            Some(("whitelist", kw)) => snapshot.whitelist.push(kw.to_string()),
+            _ => return Err(format!("baris {} tidak valid: {}", line_no, line)),
+        }
+    }
+
+    Ok(snapshot)
+}
+
+fn validate_keywords(name: &str, keywords: &mut Vec<String>) -> Result<(), String> {
+    for kw in keywords.iter_mut() {
+        *kw = normalize_keyword(kw);
+    }
+    keywords.retain(|kw| !kw.is_empty());
+    keywords.sort();
+    keywords.dedup();
+
+    if keywords.len() > MAX_KEYWORDS {
+        return Err(format!("{} melebihi {} keyword.", name, MAX_KEYWORDS));
+    }
+    if let Some(kw) = keywords.iter().find(|kw| kw.chars().count() > MAX_KEYWORD_LEN) {
+        return Err(format!("keyword {} terlalu panjang: {}", name, kw));
+    }
+
+    Ok(())
+}
+
+pub fn parse_snapshot(content: &str, is_csv: bool) -> Result<GroupSnapshot, String> {
+    let mut snapshot = if is_csv {
+        parse_csv(content)?
+    } else {
+        serde_json::from_str(content).map_err(|e| format!("JSON tidak valid: {}", e))?
+    };
+
+    validate_keywords("blacklist", &mut snapshot.blacklist)?;
+    validate_keywords("whitelist", &mut snapshot.whitelist)?;
+    Ok(snapshot)
+}
+
+// Bagian settings yang boleh dipindahkan lewat file atau template: threshold,
+// detector, dan hukuman. Field lain (mode, log channel, bahasa, moderator, dan
+// sebagainya) milik grup target dan diabaikan.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SettingsImport {
+    action: Option<ActionPolicy>,
+    strike_limit: Option<i64>,
+    detectors: Option<BTreeMap<String, bool>>,
+    emoji_threshold: Option<i64>,
+    name_score_threshold: Option<i64>,
+    spam_threshold: Option<f64>,
+}
+
+fn check_range<T: PartialOrd + std::fmt::Display>(name: &str, value: T, bounds: &RangeInclusive<T>) -> Result<(), String> {
+    if bounds.contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("{} harus {}-{}.", name, bounds.start(), bounds.end()))
+    }
+}
+
+impl SettingsImport {
+    fn parse(value: &serde_json::Value) -> Result<Self, String> {
+        if !value.is_object() {
+            return Err("settings harus berupa object.".to_string());
+        }
+        let settings: Self = serde_json::from_value(value.clone()).map_err(|e| format!("settings tidak valid: {}", e))?;
+
+        if let Some(limit) = settings.strike_limit {
+            check_range("strike_limit", limit, &STRIKE_LIMITS)?;
+        }
+        if let Some(threshold) = settings.emoji_threshold {
+            check_range("emoji_threshold", threshold, &EMOJI_THRESHOLDS)?;
+        }
+        if let Some(threshold) = settings.name_score_threshold {
+            check_range("name_score_threshold", threshold, &NAME_SCORE_THRESHOLDS)?;
+        }
+        if let Some(threshold) = settings.spam_threshold {
+            check_range("spam_threshold", threshold, &SPAM_THRESHOLDS)?;
+        }
+        let mut detectors = settings.detectors.iter().flatten().map(|(name, _)| name);
+        if let Some(name) = detectors.find(|name| !DetectorToggles::NAMES.contains(&name.as_str())) {
+            return Err(format!("detector tidak dikenal: {}", name));
+        }
+        Ok(settings)
+    }
+
+    // Field $set untuk update_settings; detector ditulis satu per satu
+    fn to_fields(&self) -> Document {
+        let mut fields = Document::new();
+        if let Some(action) = self.action {
+            fields.insert("action", action.as_str());
+        }
+        if let Some(limit) = self.strike_limit {
+            fields.insert("strike_limit", limit);
+        }
+        for (name, enabled) in self.detectors.iter().flatten() {
+            fields.insert(format!("detectors.{}", name), *enabled);
+        }
+        if let Some(threshold) = self.emoji_threshold {
+            fields.insert("emoji_threshold", threshold);
+        }
+        if let Some(threshold) = self.name_score_threshold {
+            fields.insert("name_score_threshold", threshold);
+        }
+        if let Some(threshold) = self.spam_threshold {
+            fields.insert("spam_threshold", threshold);
+        }
+        fields
+    }
+
+    // Hak untuk field yang benar-benar berubah, mengikuti tombol padanannya di
+    // panel /settings: melemahkan proteksi hanya untuk pemilik grup
+    fn required_permissions(&self, current: &GroupSettings) -> Vec<Permission> {
+        let mut needed = Vec::new();
+        if self.action.is_some_and(|a| a != current.action) || self.strike_limit.is_some_and(|l| l != current.strike_limit) {
+            needed.push(Permission::RestrictMembers);
+        }
+
+        let detectors: Vec<(bool, bool)> = self
+            .detectors
+            .iter()
+            .flatten()
+            .map(|(name, &enabled)| (current.detectors.get(name).unwrap_or(false), enabled))
+            .collect();
+        let thresholds = [
+            self.emoji_threshold.map(|t| (current.emoji_threshold as f64, t as f64)),
+            self.name_score_threshold.map(|t| (current.name_score_threshold as f64, t as f64)),
+            self.spam_threshold.map(|t| (current.spam_threshold, t)),
+        ];
+        let thresholds: Vec<(f64, f64)> = thresholds.into_iter().flatten().collect();
+
+        let weakens = detectors.iter().any(|&(was, now)| was && !now) || thresholds.iter().any(|&(was, now)| now > was);
+        let strengthens = detectors.iter().any(|&(was, now)| !was && now) || thresholds.iter().any(|&(was, now)| now < was);
+        if weakens {
+            needed.push(Permission::Creator);
+        }
+        if strengthens {
+            needed.push(Permission::DeleteMessages);
+        }
+        needed
+    }
+}
+
+pub async fn apply_snapshot(db: &Database, group_id: i64, snapshot: GroupSnapshot, mode: ImportMode) -> Result<ImportSummary, ImportError> {
+    // Validasi settings dulu supaya import yang gagal tidak mengubah apa pun
+    let settings = match &snapshot.settings {
+        Some(value) => SettingsImport::parse(value)?.to_fields(),
+        None => Document::new(),
+    };
+
+    let mut summary = ImportSummary::default();
+
+    if mode == ImportMode::Replace {
+        summary.blacklist_removed = db.clear_blacklist(group_id).await?;
+        summary.whitelist_removed = db.clear_whitelist(group_id).await?;
+    }
+
+    let (existing_bl, existing_wl) = tokio::join!(db.list_blacklist(group_id), db.list_whitelist(group_id));
+    let (existing_bl, existing_wl) = (existing_bl?, existing_wl?);
+    let is_new = |existing: &[String], kw: &String| !existing.contains(kw);
+
+    let new_bl: Vec<String> = snapshot.blacklist.into_iter().filter(|kw| is_new(&existing_bl, kw)).collect();
+    let new_wl: Vec<String> = snapshot.whitelist.into_iter().filter(|kw| is_new(&existing_wl, kw)).collect();
+    summary.blacklist_added = new_bl.len();
+    summary.whitelist_added = new_wl.len();
+
+    db.add_blacklist_many(group_id, new_bl).await?;
+    db.add_whitelist_many(group_id, new_wl).await?;
+
+    if !settings.is_empty() {
+        db.import_settings(group_id, settings).await?;
+        summary.settings_updated = true;
+    }
+
+    Ok(summary)
+}
+
+// Hak untuk perubahan daftar keyword, sama dengan command padanannya:
+// menambah whitelist atau membuang blacklist yang ada (mode replace) hanya
+// untuk pemilik grup
+fn list_permissions(snapshot: &GroupSnapshot, mode: ImportMode, blacklist: &[String], whitelist: &[String]) -> Vec<Permission> {
+    let replace = mode == ImportMode::Replace;
+    let weakens = snapshot.whitelist.iter().any(|kw| !whitelist.contains(kw))
+        || (replace && blacklist.iter().any(|kw| !snapshot.blacklist.contains(kw)));
+    let strengthens = snapshot.blacklist.iter().any(|kw| !blacklist.contains(kw))
+        || (replace && whitelist.iter().any(|kw| !snapshot.whitelist.contains(kw)));
+
+    let mut needed = Vec::new();
+    if weakens {
+        needed.push(Permission::Creator);
+    }
+    if strengthens {
+        needed.push(Permission::DeleteMessages);
+    }
+    needed
+}
+
+// apply_snapshot atas nama pengirim pesan. /importlists, /applytemplate dan
+// /clonefrom cukup butuh ChangeInfo, jadi daftar keyword dan settings di
+// dalam snapshot dicek terpisah terhadap hak pengirim di grup target.
+pub async fn apply_snapshot_as(
+    bot: &Bot,
+    db: &Database,
+    msg: &Message,
+    group_id: i64,
+    snapshot: GroupSnapshot,
+    mode: ImportMode,
+) -> Result<ImportSummary, ImportError> {
+    let (blacklist, whitelist) = tokio::join!(db.list_blacklist(group_id), db.list_whitelist(group_id));
+    let mut needed = list_permissions(&snapshot, mode, &blacklist?, &whitelist?);
+    if let Some(value) = &snapshot.settings {
+        let current = db.get_settings(group_id).await?;
+        needed.extend(SettingsImport::parse(value)?.required_permissions(&current));
+    }
+
+    for permission in needed {
+        if !permissions::sender_has(bot, db, msg, group_id, permission).await {
+            return Err(ImportError::Denied(permission));
+        }
+    }
+    apply_snapshot(db, group_id, snapshot, mode).await
+}
+
+pub async fn export_lists(bot: &Bot, db: &Database, chat_id: ChatId, group_id: i64, format: &str) -> HandlerResult {
+    let snapshot = snapshot(db, group_id).await?;
+
+    let file = if format.trim().eq_ignore_ascii_case("csv") {
+        InputFile::memory(to_csv(&snapshot)).file_name(format!("antigcast-{}.csv", group_id))
+    } else {
+        let json = serde_json::to_string_pretty(&snapshot).unwrap_or_default();
+        InputFile::memory(json).file_name(format!("antigcast-{}.json", group_id))
+    };
+
+    bot.send_document(chat_id, file).await?;
+    Ok(())
+}
+
+pub async fn import_lists(bot: &Bot, db: &Database, msg: &Message, group_id: i64, arg: &str) -> HandlerResult {
+    let mode = match ImportMode::parse(arg, ImportMode::Merge) {
+        Some(mode) => mode,
+        None => {
+            bot.send_message(msg.chat.id, "pilihan: merge (default) atau replace.").await?;
+            return Ok(());
+        }
+    };
+
+    let Some(document) = msg.reply_to_message().and_then(|m| m.document()) else {
+        bot.send_message(msg.chat.id, "balas file JSON/CSV hasil /exportlists dengan /importlists.").await?;
+        return Ok(());
+    };
+
+    if document.file.size > MAX_FILE_SIZE {
+        bot.send_message(msg.chat.id, "file terlalu besar (maksimal 1 MB).").await?;
+        return Ok(());
+    }
+
+    let file = bot.get_file(document.file.id.clone()).await?;
+    let mut content = Vec::new();
+    if let Err(e) = bot.download_file(&file.path, &mut content).await {
+        bot.send_message(msg.chat.id, format!("gagal mengunduh file: {}", e)).await?;
+        return Ok(());
+    }
+
+    let is_csv = document.file_name.as_deref().is_some_and(|n| n.to_lowercase().ends_with(".csv"));
+    let result = match String::from_utf8(content) {
+        Ok(content) => match parse_snapshot(&content, is_csv) {
+            Ok(snapshot) => apply_snapshot_as(bot, db, msg, group_id, snapshot, mode).await,
+            Err(e) => Err(e.into()),
+        },
+        Err(_) => Err(ImportError::Invalid("file bukan teks UTF-8.".to_string())),
+    };
+
+    let text = match result {
+        Ok(summary) => summary.describe(),
+        Err(ImportError::Invalid(e)) => format!("import dibatalkan: {}", e),
+        Err(ImportError::Denied(permission)) => format!("import dibatalkan: {}", permission.denied_text()),
+        Err(ImportError::Db(e)) => return Err(e.into()),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use std::sync::Arc;
+
+    #[test]
+    fn parse_snapshot_normalizes_and_dedupes() {
+        let snapshot = parse_snapshot("list,keyword\nblacklist,  Promo  VCS \nblacklist,promo vcs\nwhitelist,a,b\n", true).unwrap();
+        assert_eq!(snapshot.blacklist, vec!["promo vcs"]);
+        assert_eq!(snapshot.whitelist, vec!["a,b"]);
+    }
+
+    #[test]
+    fn parse_snapshot_rejects_invalid_csv_line() {
+        assert!(parse_snapshot("blacklist,ok\nlainnya,x\n", true).is_err());
+    }
+
+    #[test]
+    fn parse_snapshot_enforces_limits() {
+        let too_many: Vec<String> = (0..=MAX_KEYWORDS).map(|i| format!("blacklist,kw{}", i)).collect();
+        assert!(parse_snapshot(&too_many.join("\n"), true).is_err());
+
+        let at_limit: Vec<String> = (0..MAX_KEYWORDS).map(|i| format!("blacklist,kw{}", i)).collect();
+        assert!(parse_snapshot(&at_limit.join("\n"), true).is_ok());
+
+        let too_long = format!("whitelist,{}", "x".repeat(MAX_KEYWORD_LEN + 1));
+        assert!(parse_snapshot(&too_long, true).is_err());
+    }
+
+    #[test]
+    fn csv_export_round_trips() {
+        let snapshot = GroupSnapshot {
+            blacklist: vec!["promo".to_string(), "a,b".to_string()],
+            whitelist: vec!["rapat".to_string()],
+            settings: None,
+        };
+        let parsed = parse_snapshot(&to_csv(&snapshot), true).unwrap();
+        assert_eq!(parsed.blacklist, vec!["a,b", "promo"]);
+        assert_eq!(parsed.whitelist, vec!["rapat"]);
+    }
+
+    #[test]
+    fn parse_snapshot_rejects_invalid_json() {
+        assert!(parse_snapshot("{\"blacklist\": \"promo\"}", false).is_err());
+        assert!(parse_snapshot("bukan json", false).is_err());
+        assert!(parse_snapshot("{}", false).unwrap().blacklist.is_empty());
+    }
+
+    #[tokio::test]
+    async fn apply_snapshot_replace_clears_lists_first() {
+        let db = Database::with_store(Arc::new(MemoryStore::default()));
+        db.add_blacklist(1, "lama".to_string()).await.unwrap();
+        db.add_whitelist(1, "rapat".to_string()).await.unwrap();
+
+        let snapshot = GroupSnapshot { blacklist: vec!["baru".to_string()], ..Default::default() };
+        let summary = apply_snapshot(&db, 1, snapshot, ImportMode::Replace).await.ok().unwrap();
+        assert_eq!((summary.blacklist_removed, summary.whitelist_removed), (1, 1));
+        assert!(!summary.settings_updated);
+        assert_eq!(db.list_blacklist(1).await.unwrap(), vec!["baru"]);
+        assert!(db.list_whitelist(1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalid_settings_abort_import_before_changes() {
+        let db = Database::with_store(Arc::new(MemoryStore::default()));
+        let snapshot = GroupSnapshot {
+            blacklist: vec!["baru".to_string()],
+            whitelist: Vec::new(),
+            settings: Some(serde_json::json!({ "strike_limit": 0 })),
+        };
+        assert!(matches!(apply_snapshot(&db, 1, snapshot, ImportMode::Replace).await, Err(ImportError::Invalid(_))));
+        assert!(db.list_blacklist(1).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn settings_import_validates_ranges_and_detectors() {
+        assert!(SettingsImport::parse(&serde_json::json!({ "strike_limit": 0 })).is_err());
+        assert!(SettingsImport::parse(&serde_json::json!({ "spam_threshold": 0.2 })).is_err());
+        assert!(SettingsImport::parse(&serde_json::json!({ "detectors": { "unknown": true } })).is_err());
+        assert!(SettingsImport::parse(&serde_json::json!([])).is_err());
+    }
+
+    #[test]
+    fn settings_import_ignores_group_owned_fields() {
+        let value = serde_json::json!({
+            "mode": "off",
+            "log_channel": -100,
+            "language": "en",
+            "action": "ban",
+            "detectors": { "links": false },
+        });
+        let fields = SettingsImport::parse(&value).unwrap().to_fields();
+        let mut keys: Vec<&str> = fields.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, vec!["action", "detectors.links"]);
+    }
+
+    #[test]
+    fn settings_import_permissions_follow_changed_fields() {
+        let current = GroupSettings::new(1);
+        let needed = |value: serde_json::Value| SettingsImport::parse(&value).unwrap().required_permissions(&current);
+
+        assert!(needed(serde_json::json!({ "strike_limit": current.strike_limit })).is_empty());
+        assert_eq!(needed(serde_json::json!({ "action": "ban" })), vec![Permission::RestrictMembers]);
+        assert_eq!(needed(serde_json::json!({ "detectors": { "links": false } })), vec![Permission::Creator]);
+        assert_eq!(needed(serde_json::json!({ "spam_threshold": 0.99 })), vec![Permission::Creator]);
+        assert_eq!(
+            needed(serde_json::json!({ "emoji_threshold": current.emoji_threshold - 1 })),
+            vec![Permission::DeleteMessages]
+        );
+    }
+
+    #[test]
+    fn list_permissions_follow_weakening_rule() {
+        let existing_bl = vec!["promo".to_string()];
+        let existing_wl = vec!["rapat".to_string()];
+        let snapshot = |blacklist: &[&str], whitelist: &[&str]| GroupSnapshot {
+            blacklist: blacklist.iter().map(|s| s.to_string()).collect(),
+            whitelist: whitelist.iter().map(|s| s.to_string()).collect(),
+            settings: None,
+        };
+        let needed = |s: GroupSnapshot, mode| list_permissions(&s, mode, &existing_bl, &existing_wl);
+
+        assert!(needed(snapshot(&["promo"], &["rapat"]), ImportMode::Merge).is_empty());
+        assert_eq!(needed(snapshot(&["vcs"], &[]), ImportMode::Merge), vec![Permission::DeleteMessages]);
+        assert_eq!(needed(snapshot(&[], &["a"]), ImportMode::Merge), vec![Permission::Creator]);
+        // Replace membuang blacklist lama dan whitelist lama
+        assert_eq!(
+            needed(snapshot(&[], &[]), ImportMode::Replace),
+            vec![Permission::Creator, Permission::DeleteMessages]
+        );
+        assert!(needed(snapshot(&[], &[]), ImportMode::Merge).is_empty());
+    }
+
+    #[tokio::test]
+    async fn apply_snapshot_keeps_target_settings() {
+        let db = Database::with_store(Arc::new(MemoryStore::default()));
+        db.set_log_channel(1, Some(-200)).await.unwrap();
+        db.add_blacklist(1, "lama".to_string()).await.unwrap();
+
+        let snapshot = GroupSnapshot {
+            blacklist: vec!["baru".to_string()],
+            whitelist: Vec::new(),
+            settings: Some(serde_json::json!({ "log_channel": -100, "strike_limit": 5 })),
+        };
+        let summary = apply_snapshot(&db, 1, snapshot, ImportMode::Merge).await.ok().unwrap();
+        assert_eq!(summary.blacklist_added, 1);
+        assert!(summary.settings_updated);
+
+        let settings = db.get_settings(1).await.unwrap();
+        assert_eq!(settings.log_channel, Some(-200));
+        assert_eq!(settings.strike_limit, 5);
+        assert_eq!(db.list_blacklist(1).await.unwrap(), vec!["baru", "lama"]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::convert::Infallible;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{AllowedUpdate, InputFile};
+use teloxide::update_listeners::{webhooks, Polling, UpdateListener};
+use teloxide::RequestError;
+use url::Url;
+use crate::config::WebhookConfig;
+use crate::metrics;
+
+// Jenis update yang ditangani dispatcher. Telegram tidak mengirim chat_member
+// kecuali diminta secara eksplisit, padahal cache admin bergantung padanya.
+pub fn allowed_updates() -> Vec<AllowedUpdate> {
+    vec![
+        AllowedUpdate::Message,
+        AllowedUpdate::CallbackQuery,
+        AllowedUpdate::ChatMember,
+        AllowedUpdate::MyChatMember,
+    ]
+}
+
+// Long polling dengan allowed_updates yang sama seperti mode webhook
+pub async fn polling(bot: Bot) -> impl UpdateListener<Err = RequestError> {
+    Polling::builder(bot)
+        .timeout(Duration::from_secs(10))
+        .allowed_updates(allowed_updates())
+        .delete_webhook()
+        .await
+        .build()
+}
+
+// Batas backoff saat setWebhook gagal karena jaringan atau rate limit
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// Panggil setWebhook sampai berhasil. Error jaringan dan rate limit dicoba
+// lagi dengan backoff; error dari Bot API (URL, sertifikat, token salah)
+// tidak akan berubah dengan mencoba ulang, jadi langsung dikembalikan.
+async fn set_webhook(bot: &Bot, url: &Url, config: &WebhookConfig) -> Result<(), RequestError> {
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        let mut request = bot
+            .set_webhook(url.clone())
+            .secret_token(config.secret.clone())
+            .max_connections(config.max_connections)
+            .drop_pending_updates(config.drop_pending_updates)
+            .allowed_updates(allowed_updates());
+        if !config.certificate.is_empty() {
+            request = request.certificate(InputFile::file(&config.certificate));
+        }
+
+        let wait = match request.await {
+            Ok(_) => return Ok(()),
+            Err(RequestError::RetryAfter(after)) => after,
+            Err(e @ (RequestError::Network(_) | RequestError::Io(_))) => {
+                metrics::telegram_error(&e);
+                log::error!("setWebhook gagal, dicoba lagi dalam {}s: {}", backoff.as_secs(), e);
+                backoff
+            }
+            Err(e) => return Err(e),
+        };
+        tokio::time::sleep(wait).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+// Buka port `listen`, daftarkan webhook ke Telegram, lalu jalankan server
+// HTTP. Port dibuka lebih dulu supaya webhook tidak pernah terdaftar tanpa
+// server yang menerimanya. Webhook dihapus lagi saat dispatcher berhenti (Ctrl+C).
+pub async fn listener(bot: Bot, config: &WebhookConfig) -> Result<impl UpdateListener<Err = Infallible>, String> {
+    // Nilai sudah divalidasi saat konfigurasi dimuat
+    let address = config.listen.parse().expect("webhook.listen tidak valid");
+    let mut base = Url::parse(&config.url).expect("webhook.url tidak valid");
+    // Secret ditambahkan sebagai segmen path terakhir, bukan mengganti segmen yang ada
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    let url = base.join(&config.secret).expect("webhook.secret tidak valid");
+
+    let server = axum::Server::try_bind(&address)
+        .map_err(|e| format!("gagal membuka server webhook di {}: {}", config.listen, e))?;
+
+    // setWebhook dipanggil sendiri karena webhooks::axum di teloxide 0.12
+    // tidak meneruskan allowed_updates
+    set_webhook(&bot, &url, config).await.map_err(|e| format!("gagal memasang webhook: {}", e))?;
+
+    let options = webhooks::Options::new(address, url).secret_token(config.secret.clone());
+    let (mut listener, stop_flag, router) = webhooks::axum_no_setup(options);
+    let stop_token = listener.stop_token();
+
+    log::info!("Mode webhook, mendengarkan di {}", config.listen);
+    tokio::spawn(async move {
+        let result = server
+            .serve(router.into_make_service())
+            .with_graceful_shutdown(stop_flag)
+            .await;
+        if let Err(e) = result {
+            log::error!("Server webhook berhenti: {}", e);
+            stop_token.stop();
+        }
+        if let Err(e) = bot.delete_webhook().await {
+            log::error!("Gagal menghapus webhook: {}", e);
+        }
+    });
+    Ok(listener)
+}